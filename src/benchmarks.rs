@@ -0,0 +1,3963 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use polars::{
+    lazy::dsl::{avg, col, count, lit, when},
+    prelude::{
+        DataType, JoinType, LazyFrame, QuantileInterpolOptions, RollingOptions, SortOptions,
+    },
+};
+
+use serde::Deserialize;
+
+use crate::common::{CellValue, Engine, QueryResult};
+
+/// A single analytical question asked of every engine.
+///
+/// Each engine gets its own variant of the query because they disagree on how to
+/// access JSON/struct fields. Engines that can't express the query return `None`.
+pub trait Benchmark: Send + Sync {
+    /// Short identifier, e.g. `top_pages`.
+    fn name(&self) -> &'static str;
+
+    /// Human readable title printed above the results.
+    fn title(&self) -> &'static str;
+
+    /// Extra lines printed below the title.
+    fn notes(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Column labels for DuckDB results. Detected from the query when `None`.
+    fn duck_columns(&self) -> Option<Vec<&'static str>> {
+        None
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Queries `olap.events` table with the JSON payload stored as a string.
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Queries `events` table with a `jsonb` payload.
+    fn postgres_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Queries the normalized SQLite schema created by `gen_data_normalized`.
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Queries SQLite `events` with a MessagePack payload BLOB, fields are read with
+    /// `msgpack_get(payload, '$.path')`. Requires the `msgpack` cargo feature.
+    fn sqlite_msgpack_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn polars(&self, _events: LazyFrame) -> Option<LazyFrame> {
+        None
+    }
+
+    /// Same query as [`Benchmark::polars`] through the Polars SQL interface, `events` is the
+    /// parquet LazyFrame. Timings are compared with the expression API after the query.
+    fn polars_sql(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Pair of engines `(baseline, candidate)` whose timings are compared after the query.
+    fn speedup(&self) -> Option<(Engine, Engine)> {
+        None
+    }
+
+    /// Number of rows every engine must return, checked with `--verify`.
+    fn expected_rows(&self) -> Option<usize> {
+        None
+    }
+
+    /// Invariant of the result checked with `--verify`. Returns what's wrong with the result.
+    fn check(&self, _result: &QueryResult) -> Option<String> {
+        None
+    }
+
+    /// Reads days, hours or durations from `timestamp`, which only works on the native
+    /// representation. Skipped against `--timestamps epoch-millis` data.
+    fn needs_native_timestamps(&self) -> bool {
+        false
+    }
+}
+
+/// Numeric value of a cell, some engines return decimals as text.
+fn number(value: &CellValue) -> Option<f64> {
+    match value {
+        CellValue::Int(n) => Some(*n as f64),
+        CellValue::Float(n) => Some(*n),
+        CellValue::Text(s) => s.parse().ok(),
+        CellValue::Null | CellValue::Bytes(_) | CellValue::Unreadable => None,
+    }
+}
+
+fn at_most_rows(result: &QueryResult, max: usize) -> Option<String> {
+    let rows = result.rows.len();
+    (rows > max).then(|| format!("expected at most {max} rows, got {rows}"))
+}
+
+/// Check that the numbers in `columns` of every row don't decrease from left to right.
+fn non_decreasing(result: &QueryResult, columns: &[usize]) -> Option<String> {
+    for row in &result.rows {
+        let values: Vec<_> = columns.iter().map(|&i| row.get(i).and_then(number)).collect();
+        let ordered = values.windows(2).all(|w| match (w[0], w[1]) {
+            (Some(a), Some(b)) => a <= b,
+            _ => false,
+        });
+        if !ordered {
+            return Some(format!("expected non-decreasing values, got {values:?}"));
+        }
+    }
+    None
+}
+
+/// All built-in benchmarks in the order they are run.
+pub fn all() -> Vec<Box<dyn Benchmark>> {
+    all_with_params(DEFAULT_SEARCH_TERM, DEFAULT_FUNNEL_PATH).expect("Default params are valid")
+}
+
+/// Same as [`all`] with [`TextSearch`] looking for `term` and [`FormFunnel`] starting from
+/// `funnel_path`.
+pub fn all_with_params(term: &str, funnel_path: &str) -> Result<Vec<Box<dyn Benchmark>>> {
+    Ok(vec![
+        Box::new(CountByEventType),
+        Box::new(AvgPageLoadsPerSession),
+        Box::new(AvgFeedbackScore),
+        Box::new(TopPages),
+        Box::new(PageLoadsPerDay),
+        Box::new(FormSubmissions),
+        Box::new(FormSubmissionsByPage),
+        Box::new(ScorePercentiles),
+        Box::new(PageLoadsRunningTotal),
+        Box::new(AvgSessionDuration),
+        Box::new(FormFunnel::new(funnel_path)?),
+        Box::new(DistinctSessionsPerPath),
+        Box::new(ApproxDistinctSessionsPerPath),
+        Box::new(DuckJsonVsTyped),
+        Box::new(TopUserAgents),
+        Box::new(ApproxScorePercentiles),
+        Box::new(FeedbackScoreHistogram),
+        Box::new(PageLoadsDistribution),
+        Box::new(TextSearch::new(term)?),
+        Box::new(PageLoadsByHour),
+        Box::new(FormSubmissionsByTypeAndPath),
+        Box::new(BounceRate),
+        Box::new(AvgFeedbackScoreTyped),
+        Box::new(HeaviestSessions),
+        Box::new(CorrelatedPagesAboveSessionAverage),
+        Box::new(PageLoadsMovingAverage),
+        Box::new(DistinctPathsPerSession),
+    ])
+}
+
+/// Ad-hoc queries loaded from a TOML file, see [`load_queries_file`].
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueriesFile {
+    #[serde(default)]
+    query: Vec<QueryDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryDef {
+    name: String,
+    title: Option<String>,
+    sqlite: Option<String>,
+    sqlite_normalized: Option<String>,
+    duck: Option<String>,
+    duck_typed: Option<String>,
+    datafusion: Option<String>,
+    clickhouse: Option<String>,
+    postgres: Option<String>,
+    polars_sql: Option<String>,
+}
+
+/// Benchmark defined in a queries file. Strings are leaked since the trait hands out
+/// `&'static str` and the file is loaded once per run.
+pub struct FileBenchmark {
+    name: &'static str,
+    title: &'static str,
+    sqlite: Option<&'static str>,
+    sqlite_normalized: Option<&'static str>,
+    duck: Option<&'static str>,
+    duck_typed: Option<&'static str>,
+    datafusion: Option<&'static str>,
+    clickhouse: Option<&'static str>,
+    postgres: Option<&'static str>,
+    polars_sql: Option<&'static str>,
+}
+
+impl From<QueryDef> for FileBenchmark {
+    fn from(def: QueryDef) -> Self {
+        let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+        Self {
+            title: leak(def.title.unwrap_or_else(|| def.name.clone())),
+            name: leak(def.name),
+            sqlite: def.sqlite.map(leak),
+            sqlite_normalized: def.sqlite_normalized.map(leak),
+            duck: def.duck.map(leak),
+            duck_typed: def.duck_typed.map(leak),
+            datafusion: def.datafusion.map(leak),
+            clickhouse: def.clickhouse.map(leak),
+            postgres: def.postgres.map(leak),
+            polars_sql: def.polars_sql.map(leak),
+        }
+    }
+}
+
+impl FileBenchmark {
+    /// Engines without SQL for this query.
+    fn missing_engines(&self) -> Vec<Engine> {
+        [
+            (Engine::Sqlite, self.sqlite),
+            (Engine::SqliteNormalized, self.sqlite_normalized),
+            (Engine::Duck, self.duck),
+            (Engine::DuckTyped, self.duck_typed),
+            (Engine::DataFusion, self.datafusion),
+            (Engine::ClickHouse, self.clickhouse),
+            (Engine::Postgres, self.postgres),
+        ]
+        .into_iter()
+        .filter(|(_, sql)| sql.is_none())
+        .map(|(engine, _)| engine)
+        .collect()
+    }
+}
+
+impl Benchmark for FileBenchmark {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        self.sqlite
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        self.sqlite_normalized
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        self.duck
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        self.duck_typed
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        self.datafusion
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        self.clickhouse
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        self.postgres
+    }
+
+    fn polars_sql(&self) -> Option<&'static str> {
+        self.polars_sql
+    }
+}
+
+/// Load named queries with per-engine SQL from a TOML file:
+///
+/// ```toml
+/// [[query]]
+/// name = "count_events"
+/// title = "Number of events"
+/// sqlite = "SELECT COUNT(*) AS count FROM events"
+/// duck = "SELECT COUNT(*) AS count FROM events"
+/// ```
+///
+/// Engines without SQL for a query are skipped with a warning. The Polars expression API can't
+/// be used from a file, `polars_sql` runs the query through the Polars SQL interface instead.
+pub fn load_queries_file(path: &Path) -> Result<Vec<Box<dyn Benchmark>>> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read queries file {}", path.display()))?;
+    let file: QueriesFile = toml::from_str(&data)
+        .with_context(|| format!("Invalid queries file {}", path.display()))?;
+
+    let builtin: Vec<_> = all().iter().map(|b| b.name()).collect();
+    let mut benches: Vec<Box<dyn Benchmark>> = Vec::with_capacity(file.query.len());
+    for def in file.query {
+        let bench = FileBenchmark::from(def);
+        if builtin.contains(&bench.name) || benches.iter().any(|b| b.name() == bench.name) {
+            bail!("Duplicate query name {} in {}", bench.name, path.display());
+        }
+
+        let missing = bench.missing_engines();
+        if !missing.is_empty() {
+            let labels: Vec<_> = missing.iter().map(|e| e.label()).collect();
+            let labels = labels.join(", ");
+            tracing::warn!("Query {} has no SQL for {labels}, skipping them", bench.name);
+        }
+        benches.push(Box::new(bench));
+    }
+    Ok(benches)
+}
+
+const COUNT_BY_EVENT_TYPE_SQL: &str = r#"
+SELECT event_type, count(*) as count
+  FROM events
+ GROUP BY event_type
+ ORDER BY count DESC
+"#;
+
+pub struct CountByEventType;
+
+impl Benchmark for CountByEventType {
+    fn name(&self) -> &'static str {
+        "count_by_event_type"
+    }
+
+    fn title(&self) -> &'static str {
+        "Count by event_type"
+    }
+
+    /// One row per event type: page_load, chat_message and form_submit.
+    fn expected_rows(&self) -> Option<usize> {
+        Some(3)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        let known = ["page_load", "chat_message", "form_submit"];
+        result.rows.iter().find_map(|row| match row.first() {
+            Some(CellValue::Text(t)) if known.contains(&t.as_str()) => None,
+            other => Some(format!("unexpected event type {other:?}")),
+        })
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn sqlite_msgpack_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT event_type, count(*) AS count
+  FROM olap.events
+ GROUP BY event_type
+ ORDER BY count DESC
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT event_type, count(*) as count
+  FROM events
+  JOIN event_types USING (event_id)
+ GROUP BY event_type
+ ORDER BY count DESC
+"#,
+        )
+    }
+
+    fn polars_sql(&self) -> Option<&'static str> {
+        Some(COUNT_BY_EVENT_TYPE_SQL)
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .groupby([col("event_type")])
+                .agg([count().alias("count")])
+                .sort(
+                    "count",
+                    SortOptions {
+                        descending: true,
+                        ..Default::default()
+                    },
+                ),
+        )
+    }
+}
+
+const AVG_PAGE_LOADS_PER_SESSION_SQL: &str = r#"
+WITH session_loads AS (
+  SELECT session_id, count(*) as count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT AVG(count), MIN(count), MAX(count) FROM session_loads
+"#;
+
+pub struct AvgPageLoadsPerSession;
+
+impl Benchmark for AvgPageLoadsPerSession {
+    fn name(&self) -> &'static str {
+        "avg_page_loads_per_session"
+    }
+
+    fn title(&self) -> &'static str {
+        "Average page loads per session"
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn sqlite_msgpack_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH session_loads AS (
+  SELECT session_id, count(*) AS loads
+    FROM olap.events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT AVG(loads), MIN(loads), MAX(loads) FROM session_loads
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH session_loads AS (
+  SELECT session_id, count(*) as count
+    FROM events
+    JOIN event_types USING (event_id)
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT AVG(count), MIN(count), MAX(count) FROM session_loads
+"#,
+        )
+    }
+
+    fn polars_sql(&self) -> Option<&'static str> {
+        Some(AVG_PAGE_LOADS_PER_SESSION_SQL)
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                // First part
+                .filter(col("event_type").eq(lit("page_load")))
+                .groupby([col("session_id")])
+                .agg([count().alias("count")])
+                // Second part
+                .select([
+                    avg("count").alias("average"),
+                    col("count").min().alias("min"),
+                    col("count").max().alias("max"),
+                ]),
+        )
+    }
+}
+
+pub struct AvgFeedbackScore;
+
+impl Benchmark for AvgFeedbackScore {
+    fn name(&self) -> &'static str {
+        "avg_feedback_score"
+    }
+
+    fn title(&self) -> &'static str {
+        "Average feedback score"
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        let average = result.rows.first().and_then(|row| row.first()).and_then(number)?;
+        (!(0.0..=100.0).contains(&average))
+            .then(|| format!("expected an average between 0 and 100, got {average}"))
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(payload->>'$.fields[0].value') AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND payload->>'$.form_type' = 'feedback'
+"#,
+        )
+    }
+
+    fn sqlite_msgpack_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(msgpack_get(payload, '$.fields[0].value')) AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND msgpack_get(payload, '$.form_type') = 'feedback'
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH form_submissions AS (
+    SELECT payload->'$.fields' AS fields, payload->>'$.form_type' as form_type
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT AVG(TRY_CAST(fields->0->>'value' AS INTEGER)) AS average
+  FROM form_submissions
+ WHERE form_type = 'feedback'
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(TRY_CAST(payload.fields[1].value AS INTEGER)) AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND payload.form_type = 'feedback'
+"#,
+        )
+    }
+
+    /// DataFusion can't access fields of structs nested in a list, see `json_get` UDF.
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(TRY_CAST(json_get(payload, '$.fields[0].value') AS INTEGER)) AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND json_get(payload, '$.form_type') = 'feedback'
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(toInt32OrNull(JSONExtractString(payload, 'fields', 1, 'value'))) AS average
+  FROM olap.events
+ WHERE
+     event_type = 'form_submit'
+     AND JSONExtractString(payload, 'form_type') = 'feedback'
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG((payload->'fields'->0->>'value')::int) AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND payload->>'form_type' = 'feedback'
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(score) AS average
+  FROM events
+  JOIN event_types USING (event_id)
+  JOIN form_types USING (form_id)
+ WHERE
+     event_type = 'form_submit'
+     AND form_type = 'feedback'
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(
+                    col("event_type").eq(lit("form_submit")).and(
+                        col("payload")
+                            .struct_()
+                            .field_by_name("form_type")
+                            .eq(lit("feedback")),
+                    ),
+                )
+                .select([
+                    // '$.fields[0].value
+                    col("payload")
+                        .struct_()
+                        .field_by_name("fields")
+                        .arr()
+                        .first()
+                        .struct_()
+                        .field_by_name("value")
+                        .cast(DataType::Int32)
+                        .alias("score"),
+                ])
+                .select([avg("score")]),
+        )
+    }
+}
+
+const TOP_PAGES_SQL: &str = r#"
+SELECT payload->>'$.path' AS path, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY count DESC
+ LIMIT 5
+"#;
+
+pub struct TopPages;
+
+impl Benchmark for TopPages {
+    fn name(&self) -> &'static str {
+        "top_pages"
+    }
+
+    fn title(&self) -> &'static str {
+        "Top pages"
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        at_most_rows(result, 5)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(TOP_PAGES_SQL)
+    }
+
+    fn sqlite_msgpack_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT msgpack_get(payload, '$.path') AS path, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY count DESC
+ LIMIT 5
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(TOP_PAGES_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload.path AS path, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY count DESC
+ LIMIT 5
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload['path'] AS path, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY count DESC
+ LIMIT 5
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT JSONExtractString(payload, 'path') AS path, count(*) AS count
+  FROM olap.events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY count DESC
+ LIMIT 5
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload->>'path' AS path, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY count DESC
+ LIMIT 5
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT path, count
+  FROM (SELECT path_id, count(*) AS count
+          FROM events
+          JOIN event_types USING (event_id)
+         WHERE event_type = 'page_load'
+         GROUP BY path_id
+         ORDER BY count DESC
+         LIMIT 5)
+  JOIN path_cache USING (path_id)
+ ORDER BY count DESC
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([col("payload").struct_().field_by_name("path").alias("path")])
+                .groupby([col("path")])
+                .agg([count().alias("count")])
+                .sort(
+                    "count",
+                    SortOptions {
+                        descending: true,
+                        ..Default::default()
+                    },
+                )
+                .limit(5),
+        )
+    }
+}
+
+pub struct PageLoadsPerDay;
+
+impl Benchmark for PageLoadsPerDay {
+    fn name(&self) -> &'static str {
+        "page_loads_per_day"
+    }
+
+    fn title(&self) -> &'static str {
+        "Page loads per day"
+    }
+
+    fn needs_native_timestamps(&self) -> bool {
+        true
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT date(timestamp) AS date, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH page_loads AS (
+  SELECT strftime(timestamp, '%Y-%m-%d') AS date
+    FROM events
+   WHERE event_type = 'page_load'
+)
+SELECT date, COUNT(*) AS count
+  FROM page_loads
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT strftime(timestamp, '%Y-%m-%d') AS date, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT date_trunc('day', timestamp) AS date, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT toDate(timestamp) AS date, count(*) AS count
+  FROM olap.events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT to_char(timestamp, 'YYYY-MM-DD') AS date, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT date(timestamp, 'unixepoch') AS date, COUNT(*) AS count
+  FROM events
+  JOIN event_types USING (event_id)
+ WHERE
+     event_type = 'page_load'
+ GROUP BY date
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([col("timestamp").dt().date().alias("date")])
+                .groupby([col("date")])
+                .agg([count().alias("count")])
+                .sort("date", Default::default())
+                .limit(10),
+        )
+    }
+}
+
+pub struct FormSubmissions;
+
+impl Benchmark for FormSubmissions {
+    fn name(&self) -> &'static str {
+        "form_submissions"
+    }
+
+    fn title(&self) -> &'static str {
+        "Form submissions"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &[
+            "Unique: count submission once per session id",
+            "Total: count all submission",
+        ]
+    }
+
+    /// One row per form type: contact-us and feedback.
+    fn expected_rows(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        non_decreasing(result, &[1, 2])
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT payload->>'$.form_type' as form_type, session_id, count(*) as count
+   FROM events
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, COUNT(count) as unique_count, SUM(count) as total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT payload->>'$.form_type' as form_type, session_id, count(*) as count
+   FROM events
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, COUNT(count) as unique, SUM(count) as total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT payload.form_type as form_type, session_id, count(*) as count
+   FROM events
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, COUNT(count) as unique, SUM(count) as total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT payload['form_type'] as form_type, session_id, count(*) as count
+   FROM events
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, COUNT(count) as unique, SUM(count) as total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT JSONExtractString(payload, 'form_type') AS form_type, session_id, count(*) AS submitted
+   FROM olap.events
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, count(*) AS unique_count, sum(submitted) AS total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT payload->>'form_type' AS form_type, session_id, count(*) AS count
+   FROM events
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, COUNT(count) AS unique_count, SUM(count) AS total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH submissions AS (
+  SELECT form_type, session_id, count(*) as count
+   FROM events
+   JOIN event_types USING (event_id)
+   JOIN form_types USING (form_id)
+   WHERE event_type = 'form_submit'
+   GROUP BY form_type, session_id
+)
+SELECT form_type, COUNT(count) as unique_count, SUM(count) as total
+  FROM submissions
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                // First part
+                .filter(col("event_type").eq(lit("form_submit")))
+                .select([
+                    col("payload")
+                        .struct_()
+                        .field_by_name("form_type")
+                        .alias("form_type"),
+                    col("session_id"),
+                ])
+                .groupby([col("form_type"), col("session_id")])
+                .agg([count().alias("count")])
+                // Second part
+                .groupby([col("form_type")])
+                .agg([count().alias("unique"), col("count").sum().alias("total")])
+                .sort("form_type", Default::default()),
+        )
+    }
+}
+
+pub struct FormSubmissionsByPage;
+
+impl Benchmark for FormSubmissionsByPage {
+    fn name(&self) -> &'static str {
+        "form_submissions_by_page"
+    }
+
+    fn title(&self) -> &'static str {
+        "Form submissions by page"
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload->>'$.form_type' as form_type, e2.payload->>'$.path' as path, count(*) as count
+ FROM events e1
+ LEFT JOIN events as e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+       AND path = '/after'
+ GROUP BY form_type, e2.payload->>'$.path'
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload->>'$.form_type' as form_type, e2.payload->>'$.path' as path, count(*) as count
+ FROM events e1
+ LEFT JOIN events as e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+       AND path = '/after'
+ GROUP BY form_type, path
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload.form_type as form_type, e2.payload.path as path, count(*) as count
+ FROM events e1
+ LEFT JOIN events as e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+       AND path = '/after'
+ GROUP BY form_type, path
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload['form_type'] as form_type, e2.payload['path'] as path, count(*) as count
+ FROM events e1
+ LEFT JOIN events as e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+       AND e2.payload['path'] = '/after'
+ GROUP BY form_type, path
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT JSONExtractString(e1.payload, 'form_type') AS form_type,
+       JSONExtractString(e2.payload, 'path') AS path,
+       count(*) AS count
+ FROM olap.events AS e1
+ LEFT JOIN olap.events AS e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+       AND path = '/after'
+ GROUP BY form_type, path
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload->>'form_type' AS form_type, e2.payload->>'path' AS path, count(*) AS count
+ FROM events e1
+ LEFT JOIN events AS e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+       AND e2.payload->>'path' = '/after'
+ GROUP BY 1, 2
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT form_type, path, count(*) as count
+ FROM events e1
+ JOIN form_types USING (form_id)
+ JOIN events e2 ON e1.page_id = e2.page_id
+ JOIN path_cache ON e2.path_id = path_cache.path_id
+ WHERE path = '/after'
+ GROUP BY form_type, path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        let forms_pdf = events
+            .clone()
+            .filter(col("event_type").eq(lit("form_submit")))
+            .select([
+                col("payload")
+                    .struct_()
+                    .field_by_name("form_type")
+                    .alias("form_type"),
+                col("page_id"),
+            ]);
+
+        let paths_pdf = events //
+            .filter(col("event_type").eq(lit("page_load"))) //
+            .select([
+                col("payload").struct_().field_by_name("path").alias("path"),
+                col("page_id"),
+            ]);
+
+        Some(
+            forms_pdf
+                .join(
+                    paths_pdf,
+                    [col("page_id")],
+                    [col("page_id")],
+                    JoinType::Left,
+                )
+                .filter(col("path").eq(lit("/after")))
+                .groupby([col("form_type"), col("path")])
+                .agg([count()])
+                .sort("form_type", Default::default()),
+        )
+    }
+}
+
+pub struct ScorePercentiles;
+
+impl Benchmark for ScorePercentiles {
+    fn name(&self) -> &'static str {
+        "score_percentiles"
+    }
+
+    fn title(&self) -> &'static str {
+        "Feedback score percentiles (DataFusion approximates)"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &[
+            "Exact: SQLite (emulated with window functions), DuckDB, Polars, ClickHouse",
+            "Approximate: DataFusion (approx_percentile_cont)",
+        ]
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        non_decreasing(result, &[1, 2, 3])
+    }
+
+    /// SQLite has no quantile functions. Interpolate between the two closest ranks
+    /// the same way `quantile_cont` does.
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+  SELECT payload->>'$.form_type' AS form_type,
+         CAST(payload->>'$.fields[0].value' AS INTEGER) AS score
+    FROM events
+   WHERE
+       event_type = 'form_submit'
+       AND payload->>'$.form_type' = 'feedback'
+),
+ranked AS (
+  SELECT form_type, score,
+         ROW_NUMBER() OVER (PARTITION BY form_type ORDER BY score) - 1 AS idx,
+         (COUNT(*) OVER (PARTITION BY form_type) - 1) * 0.50 AS pos50,
+         (COUNT(*) OVER (PARTITION BY form_type) - 1) * 0.95 AS pos95,
+         (COUNT(*) OVER (PARTITION BY form_type) - 1) * 0.99 AS pos99
+    FROM scores
+)
+SELECT form_type,
+       SUM(CASE
+             WHEN idx = CAST(pos50 AS INTEGER) THEN score * (1 - (pos50 - CAST(pos50 AS INTEGER)))
+             WHEN idx = CAST(pos50 AS INTEGER) + 1 THEN score * (pos50 - CAST(pos50 AS INTEGER))
+           END) AS p50,
+       SUM(CASE
+             WHEN idx = CAST(pos95 AS INTEGER) THEN score * (1 - (pos95 - CAST(pos95 AS INTEGER)))
+             WHEN idx = CAST(pos95 AS INTEGER) + 1 THEN score * (pos95 - CAST(pos95 AS INTEGER))
+           END) AS p95,
+       SUM(CASE
+             WHEN idx = CAST(pos99 AS INTEGER) THEN score * (1 - (pos99 - CAST(pos99 AS INTEGER)))
+             WHEN idx = CAST(pos99 AS INTEGER) + 1 THEN score * (pos99 - CAST(pos99 AS INTEGER))
+           END) AS p99
+  FROM ranked
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT payload->>'$.form_type' AS form_type,
+           TRY_CAST(payload->>'$.fields[0].value' AS INTEGER) AS score
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       quantile_cont(score, 0.50) AS p50,
+       quantile_cont(score, 0.95) AS p95,
+       quantile_cont(score, 0.99) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT payload.form_type AS form_type,
+           TRY_CAST(payload.fields[1].value AS INTEGER) AS score
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       quantile_cont(score, 0.50) AS p50,
+       quantile_cont(score, 0.95) AS p95,
+       quantile_cont(score, 0.99) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT json_get(payload, '$.form_type') AS form_type,
+           TRY_CAST(json_get(payload, '$.fields[0].value') AS DOUBLE) AS score
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       approx_percentile_cont(score, 0.50) AS p50,
+       approx_percentile_cont(score, 0.95) AS p95,
+       approx_percentile_cont(score, 0.99) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT JSONExtractString(payload, 'form_type') AS form_type,
+           toInt32OrNull(JSONExtractString(payload, 'fields', 1, 'value')) AS score
+      FROM olap.events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       quantileExactInclusive(0.50)(score) AS p50,
+       quantileExactInclusive(0.95)(score) AS p95,
+       quantileExactInclusive(0.99)(score) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT payload->>'form_type' AS form_type,
+           (payload->'fields'->0->>'value')::int AS score
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       percentile_cont(0.50) WITHIN GROUP (ORDER BY score) AS p50,
+       percentile_cont(0.95) WITHIN GROUP (ORDER BY score) AS p95,
+       percentile_cont(0.99) WITHIN GROUP (ORDER BY score) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        let form_type = col("payload").struct_().field_by_name("form_type");
+        let quantile = |q: f64, name: &str| {
+            col("score")
+                .quantile(lit(q), QuantileInterpolOptions::Linear)
+                .alias(name)
+        };
+
+        Some(
+            events
+                .filter(
+                    col("event_type")
+                        .eq(lit("form_submit"))
+                        .and(form_type.clone().eq(lit("feedback"))),
+                )
+                .select([
+                    form_type.alias("form_type"),
+                    col("payload")
+                        .struct_()
+                        .field_by_name("fields")
+                        .arr()
+                        .first()
+                        .struct_()
+                        .field_by_name("value")
+                        .cast(DataType::Int32)
+                        .alias("score"),
+                ])
+                .groupby([col("form_type")])
+                .agg([quantile(0.50, "p50"), quantile(0.95, "p95"), quantile(0.99, "p99")])
+                .sort("form_type", Default::default()),
+        )
+    }
+}
+
+/// Same day bucketing as [`PageLoadsPerDay`] for each engine.
+pub struct PageLoadsRunningTotal;
+
+impl Benchmark for PageLoadsRunningTotal {
+    fn name(&self) -> &'static str {
+        "page_loads_running_total"
+    }
+
+    fn title(&self) -> &'static str {
+        "Running total of page loads per day"
+    }
+
+    fn needs_native_timestamps(&self) -> bool {
+        true
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT date(timestamp) AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT date, count, SUM(count) OVER (ORDER BY date) AS running_total
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT strftime(timestamp, '%Y-%m-%d') AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT date, count, SUM(count) OVER (ORDER BY date) AS running_total
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        self.duck_sql()
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT date_trunc('day', timestamp) AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT date, count, SUM(count) OVER (ORDER BY date) AS running_total
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT toDate(timestamp) AS date, count(*) AS count
+    FROM olap.events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT date, count, sum(count) OVER (ORDER BY date) AS running_total
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT to_char(timestamp, 'YYYY-MM-DD') AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT date, count, SUM(count) OVER (ORDER BY date) AS running_total
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT date(timestamp, 'unixepoch') AS date, COUNT(*) AS count
+    FROM events
+    JOIN event_types USING (event_id)
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT date, count, SUM(count) OVER (ORDER BY date) AS running_total
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([col("timestamp").dt().date().alias("date")])
+                .groupby([col("date")])
+                .agg([count().alias("count")])
+                .sort("date", Default::default())
+                .with_column(col("count").cumsum(false).alias("running_total"))
+                .limit(10),
+        )
+    }
+}
+
+pub struct AvgSessionDuration;
+
+impl Benchmark for AvgSessionDuration {
+    fn name(&self) -> &'static str {
+        "avg_session_duration"
+    }
+
+    fn title(&self) -> &'static str {
+        "Average session duration"
+    }
+
+    fn needs_native_timestamps(&self) -> bool {
+        true
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["Duration: seconds between the first and the last event of a session"]
+    }
+
+    /// Timestamps are stored as text, `julianday` parses them into fractional days.
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT (julianday(MAX(timestamp)) - julianday(MIN(timestamp))) * 86400.0 AS duration
+    FROM events
+   GROUP BY session_id
+)
+SELECT AVG(duration) AS avg_duration, MAX(duration) AS max_duration
+  FROM sessions
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT date_diff('microsecond', MIN(timestamp), MAX(timestamp)) / 1000000.0 AS duration
+    FROM events
+   GROUP BY session_id
+)
+SELECT AVG(duration) AS avg_duration, MAX(duration) AS max_duration
+  FROM sessions
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        self.duck_sql()
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT (CAST(MAX(timestamp) AS BIGINT) - CAST(MIN(timestamp) AS BIGINT)) / 1000000.0 AS duration
+    FROM events
+   GROUP BY session_id
+)
+SELECT AVG(duration) AS avg_duration, MAX(duration) AS max_duration
+  FROM sessions
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT (toUnixTimestamp64Micro(max(timestamp)) - toUnixTimestamp64Micro(min(timestamp)))
+         / 1000000.0 AS duration
+    FROM olap.events
+   GROUP BY session_id
+)
+SELECT avg(duration) AS avg_duration, max(duration) AS max_duration
+  FROM sessions
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT EXTRACT(EPOCH FROM MAX(timestamp) - MIN(timestamp))::float8 AS duration
+    FROM events
+   GROUP BY session_id
+)
+SELECT AVG(duration) AS avg_duration, MAX(duration) AS max_duration
+  FROM sessions
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT CAST(MAX(timestamp) - MIN(timestamp) AS REAL) AS duration
+    FROM events
+   GROUP BY session_id
+)
+SELECT AVG(duration) AS avg_duration, MAX(duration) AS max_duration
+  FROM sessions
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        // Timestamps in microseconds.
+        let micros = || col("timestamp").cast(DataType::Int64);
+        let duration = (micros().max() - micros().min()).cast(DataType::Float64) / lit(1_000_000.0);
+
+        Some(
+            events
+                .groupby([col("session_id")])
+                .agg([duration.alias("duration")])
+                .select([
+                    col("duration").mean().alias("avg_duration"),
+                    col("duration").max().alias("max_duration"),
+                ]),
+        )
+    }
+}
+
+/// Path [`FormFunnel`] starts from unless another one is given. Funnels from `/before` need
+/// a `--words-file` with `before` among the first 40 words, the default words don't have it.
+pub const DEFAULT_FUNNEL_PATH: &str = "/after";
+
+/// Sessions that loaded a path and submitted a form on that page. A session counts
+/// as converted when any of its page loads on the path had a submission.
+///
+/// The path is only known at runtime, so the SQL is built once and leaked like in
+/// [`TextSearch`].
+pub struct FormFunnel {
+    path: &'static str,
+    title: &'static str,
+    notes: &'static [&'static str],
+    sqlite: &'static str,
+    sqlite_normalized: &'static str,
+    duck_typed: &'static str,
+    datafusion: &'static str,
+    clickhouse: &'static str,
+    postgres: &'static str,
+}
+
+impl FormFunnel {
+    pub fn new(path: &str) -> Result<Self> {
+        // The path is inlined into SQL, don't let it end the literal.
+        if !path.starts_with('/') || path.contains(['\'', '\\']) {
+            bail!("Funnel path must start with / and have no quotes or \\, got {path:?}");
+        }
+
+        let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+        let query = |page_loads: String, submitted: &str| {
+            leak(format!(
+                r#"
+WITH page_loads AS (
+  SELECT session_id, page_id
+    FROM {page_loads}
+),
+submitted AS (
+  SELECT DISTINCT page_id
+    FROM {submitted}
+),
+sessions AS (
+  SELECT p.session_id, MAX(CASE WHEN s.page_id IS NULL THEN 0 ELSE 1 END) AS converted
+    FROM page_loads p
+    LEFT JOIN submitted s ON p.page_id = s.page_id
+   GROUP BY p.session_id
+)
+SELECT COUNT(*) AS sessions,
+       SUM(converted) AS converted,
+       1.0 * SUM(converted) / COUNT(*) AS conversion_rate
+  FROM sessions
+"#
+            ))
+        };
+        let page_loads = |field: &str| {
+            format!("events\n   WHERE event_type = 'page_load' AND {field} = '{path}'")
+        };
+        let form_submits = "events\n   WHERE event_type = 'form_submit'";
+        Ok(Self {
+            path: leak(path.to_string()),
+            title: leak(format!("Funnel: page load on {path} followed by a form submission")),
+            notes: Box::leak(Box::new([
+                leak(format!("Sessions: sessions with a page load on {path}")),
+                "Converted: sessions that submitted a form on one of those pages",
+            ])),
+            sqlite: query(page_loads("payload->>'$.path'"), form_submits),
+            sqlite_normalized: query(
+                format!("events\n    JOIN path_cache USING (path_id)\n   WHERE path = '{path}'"),
+                "events\n   WHERE form_id IS NOT NULL",
+            ),
+            duck_typed: query(page_loads("payload.path"), form_submits),
+            datafusion: query(page_loads("payload['path']"), form_submits),
+            // Unmatched rows of a LEFT JOIN get default values instead of NULL in ClickHouse.
+            clickhouse: leak(format!(
+                r#"
+WITH page_loads AS (
+  SELECT session_id, page_id
+    FROM olap.events
+   WHERE event_type = 'page_load' AND JSONExtractString(payload, 'path') = '{path}'
+),
+submitted AS (
+  SELECT DISTINCT page_id
+    FROM olap.events
+   WHERE event_type = 'form_submit'
+),
+sessions AS (
+  SELECT p.session_id, max(s.page_id != '') AS converted
+    FROM page_loads AS p
+    LEFT JOIN submitted AS s ON p.page_id = s.page_id
+   GROUP BY p.session_id
+)
+SELECT count(*) AS sessions,
+       sum(converted) AS converted,
+       sum(converted) / count(*) AS conversion_rate
+  FROM sessions
+"#
+            )),
+            postgres: query(page_loads("payload->>'path'"), form_submits),
+        })
+    }
+}
+
+impl Benchmark for FormFunnel {
+    fn name(&self) -> &'static str {
+        "form_funnel"
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        self.notes
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        non_decreasing(result, &[1, 0])
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(self.sqlite)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(self.sqlite)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(self.duck_typed)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(self.datafusion)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(self.clickhouse)
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(self.postgres)
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(self.sqlite_normalized)
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        let page_loads = events
+            .clone()
+            .filter(
+                col("event_type").eq(lit("page_load")).and(
+                    col("payload")
+                        .struct_()
+                        .field_by_name("path")
+                        .eq(lit(self.path)),
+                ),
+            )
+            .select([col("session_id"), col("page_id")]);
+
+        let submitted = events
+            .filter(col("event_type").eq(lit("form_submit")))
+            .groupby([col("page_id")])
+            .agg([count().alias("forms")]);
+
+        Some(
+            page_loads
+                .join(
+                    submitted,
+                    [col("page_id")],
+                    [col("page_id")],
+                    JoinType::Left,
+                )
+                .groupby([col("session_id")])
+                .agg([col("forms")
+                    .is_not_null()
+                    .cast(DataType::Int64)
+                    .max()
+                    .alias("converted")])
+                .select([
+                    count().alias("sessions"),
+                    col("converted").sum().alias("converted"),
+                    (col("converted").sum().cast(DataType::Float64)
+                        / count().cast(DataType::Float64))
+                    .alias("conversion_rate"),
+                ]),
+        )
+    }
+}
+
+const DISTINCT_SESSIONS_PER_PATH_SQL: &str = r#"
+SELECT payload->>'$.path' AS path, COUNT(DISTINCT session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#;
+
+pub struct DistinctSessionsPerPath;
+
+impl Benchmark for DistinctSessionsPerPath {
+    fn name(&self) -> &'static str {
+        "distinct_sessions_per_path"
+    }
+
+    fn title(&self) -> &'static str {
+        "Unique sessions per page (exact)"
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(DISTINCT_SESSIONS_PER_PATH_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(DISTINCT_SESSIONS_PER_PATH_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload.path AS path, COUNT(DISTINCT session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT json_get(payload, '$.path') AS path, COUNT(DISTINCT session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT JSONExtractString(payload, 'path') AS path, uniqExact(session_id) AS sessions
+  FROM olap.events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload->>'path' AS path, COUNT(DISTINCT session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT path, COUNT(DISTINCT session_id) AS sessions
+  FROM events
+  JOIN path_cache USING (path_id)
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([
+                    col("payload").struct_().field_by_name("path").alias("path"),
+                    col("session_id"),
+                ])
+                .groupby([col("path")])
+                .agg([col("session_id").n_unique().alias("sessions")])
+                .sort("path", Default::default()),
+        )
+    }
+}
+
+/// Same as [`DistinctSessionsPerPath`] using HyperLogLog based approximations.
+/// Only engines with an approximate distinct count implement it.
+pub struct ApproxDistinctSessionsPerPath;
+
+impl Benchmark for ApproxDistinctSessionsPerPath {
+    fn name(&self) -> &'static str {
+        "approx_distinct_sessions_per_path"
+    }
+
+    fn title(&self) -> &'static str {
+        "Unique sessions per page (approximate)"
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload->>'$.path' AS path, approx_count_distinct(session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload.path AS path, approx_count_distinct(session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT json_get(payload, '$.path') AS path, approx_distinct(session_id) AS sessions
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT JSONExtractString(payload, 'path') AS path, uniq(session_id) AS sessions
+  FROM olap.events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY path
+ ORDER BY path
+"#,
+        )
+    }
+}
+
+/// [`AvgFeedbackScore`] on DuckDB only, comparing the JSON payload with the typed struct.
+pub struct DuckJsonVsTyped;
+
+impl Benchmark for DuckJsonVsTyped {
+    fn name(&self) -> &'static str {
+        "duck_json_vs_typed"
+    }
+
+    fn title(&self) -> &'static str {
+        "Average feedback score (DuckDB JSON vs typed payload)"
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        AvgFeedbackScore.duck_sql()
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        AvgFeedbackScore.duck_typed_sql()
+    }
+
+    fn speedup(&self) -> Option<(Engine, Engine)> {
+        Some((Engine::Duck, Engine::DuckTyped))
+    }
+}
+
+const TOP_USER_AGENTS_SQL: &str = r#"
+SELECT payload->>'$.user_agent' AS user_agent, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY user_agent
+ ORDER BY count DESC
+ LIMIT 10
+"#;
+
+pub struct TopUserAgents;
+
+impl Benchmark for TopUserAgents {
+    fn name(&self) -> &'static str {
+        "top_user_agents"
+    }
+
+    fn title(&self) -> &'static str {
+        "Top user agents"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["The normalized schema joins the user_agents lookup table."]
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        at_most_rows(result, 10)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(TOP_USER_AGENTS_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(TOP_USER_AGENTS_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload.user_agent AS user_agent, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY user_agent
+ ORDER BY count DESC
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT json_get(payload, '$.user_agent') AS user_agent, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY user_agent
+ ORDER BY count DESC
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT JSONExtractString(payload, 'user_agent') AS user_agent, count(*) AS count
+  FROM olap.events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY user_agent
+ ORDER BY count DESC
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT payload->>'user_agent' AS user_agent, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY user_agent
+ ORDER BY count DESC
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT user_agent, COUNT(*) AS count
+  FROM events
+  JOIN event_types USING (event_id)
+  JOIN user_agents USING (user_agent_id)
+ WHERE
+     event_type = 'page_load'
+ GROUP BY user_agent
+ ORDER BY count DESC
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([col("payload")
+                    .struct_()
+                    .field_by_name("user_agent")
+                    .alias("user_agent")])
+                .groupby([col("user_agent")])
+                .agg([count().alias("count")])
+                .sort(
+                    "count",
+                    SortOptions {
+                        descending: true,
+                        ..Default::default()
+                    },
+                )
+                .limit(10),
+        )
+    }
+}
+
+pub struct ApproxScorePercentiles;
+
+impl Benchmark for ApproxScorePercentiles {
+    fn name(&self) -> &'static str {
+        "approx_score_percentiles"
+    }
+
+    fn title(&self) -> &'static str {
+        "Approximate feedback score percentiles"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &[
+            "SQLite: approx_quantile UDF over a 10k reservoir sample, exact up to 10k rows,",
+            "  otherwise the rank is within ±1.4% of q with 95% confidence",
+            "DuckDB: approx_quantile (T-Digest), DataFusion: approx_percentile_cont (T-Digest)",
+            "ClickHouse: quantile (reservoir sampling of 8192 values)",
+        ]
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        non_decreasing(result, &[1, 2, 3])
+    }
+
+    /// Relies on `approx_quantile` registered by `common::register_sqlite_functions`.
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+  SELECT payload->>'$.form_type' AS form_type,
+         CAST(payload->>'$.fields[0].value' AS INTEGER) AS score
+    FROM events
+   WHERE
+       event_type = 'form_submit'
+       AND payload->>'$.form_type' = 'feedback'
+)
+SELECT form_type,
+       approx_quantile(score, 0.50) AS p50,
+       approx_quantile(score, 0.95) AS p95,
+       approx_quantile(score, 0.99) AS p99
+  FROM scores
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT form_type,
+       approx_quantile(score, 0.50) AS p50,
+       approx_quantile(score, 0.95) AS p95,
+       approx_quantile(score, 0.99) AS p99
+  FROM events
+  JOIN event_types USING (event_id)
+  JOIN form_types USING (form_id)
+ WHERE
+     event_type = 'form_submit'
+     AND form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT payload->>'$.form_type' AS form_type,
+           TRY_CAST(payload->>'$.fields[0].value' AS INTEGER) AS score
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       approx_quantile(score, 0.50) AS p50,
+       approx_quantile(score, 0.95) AS p95,
+       approx_quantile(score, 0.99) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT payload.form_type AS form_type,
+           TRY_CAST(payload.fields[1].value AS INTEGER) AS score
+      FROM events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       approx_quantile(score, 0.50) AS p50,
+       approx_quantile(score, 0.95) AS p95,
+       approx_quantile(score, 0.99) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        ScorePercentiles.datafusion_sql()
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT JSONExtractString(payload, 'form_type') AS form_type,
+           toInt32OrNull(JSONExtractString(payload, 'fields', 1, 'value')) AS score
+      FROM olap.events
+     WHERE event_type = 'form_submit'
+)
+SELECT form_type,
+       quantile(0.50)(score) AS p50,
+       quantile(0.95)(score) AS p95,
+       quantile(0.99)(score) AS p99
+  FROM scores
+ WHERE form_type = 'feedback'
+ GROUP BY form_type
+ ORDER BY form_type
+"#,
+        )
+    }
+}
+
+pub struct FeedbackScoreHistogram;
+
+impl Benchmark for FeedbackScoreHistogram {
+    fn name(&self) -> &'static str {
+        "feedback_score_histogram"
+    }
+
+    fn title(&self) -> &'static str {
+        "Feedback scores bucketed into ranges of 20"
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        at_most_rows(result, 5)
+    }
+
+    /// SQLite has no bucketing functions, ranges are spelled out with CASE.
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+  SELECT CAST(payload->>'$.fields[0].value' AS INTEGER) AS score
+    FROM events
+   WHERE
+       event_type = 'form_submit'
+       AND payload->>'$.form_type' = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+  SELECT score
+    FROM events
+    JOIN event_types USING (event_id)
+    JOIN form_types USING (form_id)
+   WHERE
+       event_type = 'form_submit'
+       AND form_type = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    /// `width_bucket` isn't available in DuckDB 0.7 and `histogram` returns a single map,
+    /// so this uses the same CASE expression as SQLite.
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT TRY_CAST(payload->>'$.fields[0].value' AS INTEGER) AS score
+      FROM events
+     WHERE
+         event_type = 'form_submit'
+         AND payload->>'$.form_type' = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT TRY_CAST(payload.fields[1].value AS INTEGER) AS score
+      FROM events
+     WHERE
+         event_type = 'form_submit'
+         AND payload.form_type = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT TRY_CAST(json_get(payload, '$.fields[0].value') AS INTEGER) AS score
+      FROM events
+     WHERE
+         event_type = 'form_submit'
+         AND json_get(payload, '$.form_type') = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT toInt32OrNull(JSONExtractString(payload, 'fields', 1, 'value')) AS score
+      FROM olap.events
+     WHERE
+         event_type = 'form_submit'
+         AND JSONExtractString(payload, 'form_type') = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH scores AS (
+    SELECT (payload->'fields'->0->>'value')::int AS score
+      FROM events
+     WHERE
+         event_type = 'form_submit'
+         AND payload->>'form_type' = 'feedback'
+)
+SELECT CASE
+         WHEN score <= 20 THEN '0-20'
+         WHEN score <= 40 THEN '21-40'
+         WHEN score <= 60 THEN '41-60'
+         WHEN score <= 80 THEN '61-80'
+         ELSE '81-100'
+       END AS bucket,
+       COUNT(*) AS count
+  FROM scores
+ GROUP BY bucket
+ ORDER BY MIN(score)
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        let score = col("score");
+        let bucket = when(score.clone().lt_eq(lit(20)))
+            .then(lit("0-20"))
+            .when(score.clone().lt_eq(lit(40)))
+            .then(lit("21-40"))
+            .when(score.clone().lt_eq(lit(60)))
+            .then(lit("41-60"))
+            .when(score.clone().lt_eq(lit(80)))
+            .then(lit("61-80"))
+            .otherwise(lit("81-100"));
+
+        Some(
+            events
+                .filter(
+                    col("event_type").eq(lit("form_submit")).and(
+                        col("payload")
+                            .struct_()
+                            .field_by_name("form_type")
+                            .eq(lit("feedback")),
+                    ),
+                )
+                .select([col("payload")
+                    .struct_()
+                    .field_by_name("fields")
+                    .arr()
+                    .first()
+                    .struct_()
+                    .field_by_name("value")
+                    .cast(DataType::Int32)
+                    .alias("score")])
+                .groupby([bucket.alias("bucket")])
+                .agg([count().alias("count"), score.min().alias("lower")])
+                .sort("lower", Default::default())
+                .select([col("bucket"), col("count")]),
+        )
+    }
+}
+
+const PAGE_LOADS_DISTRIBUTION_SQL: &str = r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(*) AS page_loads
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+),
+total AS (
+  SELECT COUNT(*) AS total FROM sessions
+)
+SELECT page_loads, COUNT(*) AS sessions, ROUND(100.0 * COUNT(*) / total, 1) AS percent
+  FROM sessions, total
+ GROUP BY page_loads, total
+ ORDER BY page_loads
+"#;
+
+pub struct PageLoadsDistribution;
+
+impl Benchmark for PageLoadsDistribution {
+    fn name(&self) -> &'static str {
+        "page_loads_distribution"
+    }
+
+    fn title(&self) -> &'static str {
+        "Distribution of page loads per session"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["Default weights: 1 => 40%, 2 => 30%, 4 => 20%, 8 => 8%, 12 => 2%"]
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        let total: f64 = result.rows.iter().filter_map(|row| row.get(2).and_then(number)).sum();
+        // Each percentage is rounded to one decimal.
+        let tolerance = 0.05 * result.rows.len() as f64;
+        ((total - 100.0).abs() > tolerance)
+            .then(|| format!("expected percentages to sum to 100, got {total:.1}"))
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(PAGE_LOADS_DISTRIBUTION_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(PAGE_LOADS_DISTRIBUTION_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(PAGE_LOADS_DISTRIBUTION_SQL)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(PAGE_LOADS_DISTRIBUTION_SQL)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(*) AS page_loads
+    FROM olap.events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+),
+total AS (
+  SELECT COUNT(*) AS total FROM sessions
+)
+SELECT page_loads, COUNT(*) AS sessions, ROUND(100.0 * COUNT(*) / total, 1) AS percent
+  FROM sessions, total
+ GROUP BY page_loads, total
+ ORDER BY page_loads
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(PAGE_LOADS_DISTRIBUTION_SQL)
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(*) AS page_loads
+    FROM events
+    JOIN event_types USING (event_id)
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+),
+total AS (
+  SELECT COUNT(*) AS total FROM sessions
+)
+SELECT page_loads, COUNT(*) AS sessions, ROUND(100.0 * COUNT(*) / total, 1) AS percent
+  FROM sessions, total
+ GROUP BY page_loads, total
+ ORDER BY page_loads
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        let sessions = col("sessions").cast(DataType::Float64);
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .groupby([col("session_id")])
+                .agg([count().alias("page_loads")])
+                .groupby([col("page_loads")])
+                .agg([count().alias("sessions")])
+                .with_column(
+                    (sessions.clone() * lit(100.0) / sessions.sum())
+                        .round(1)
+                        .alias("percent"),
+                )
+                .sort("page_loads", Default::default()),
+        )
+    }
+}
+
+/// Word searched for by [`TextSearch`] unless another one is given.
+pub const DEFAULT_SEARCH_TERM: &str = "dragon";
+
+/// Count chat messages whose text contains a word. Unlike the other queries this scans
+/// the whole text column instead of extracting short JSON values.
+///
+/// The term is only known at runtime, so the SQL is built once and leaked like in
+/// [`FileBenchmark`].
+pub struct TextSearch {
+    term: &'static str,
+    title: &'static str,
+    sqlite: &'static str,
+    sqlite_normalized: &'static str,
+    duck: &'static str,
+    duck_typed: &'static str,
+    datafusion: &'static str,
+    clickhouse: &'static str,
+    postgres: &'static str,
+}
+
+impl TextSearch {
+    pub fn new(term: &str) -> Result<Self> {
+        // The term is inlined into SQL, don't let it end the literal or add wildcards.
+        if term.is_empty() || term.contains(['\'', '%', '_', '\\']) {
+            bail!("Search term must be non-empty without quotes, %, _ or \\, got {term:?}");
+        }
+
+        let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+        let query = |table: &str, text: &str| {
+            leak(format!(
+                r#"
+SELECT COUNT(*) AS count
+  FROM {table}
+ WHERE
+     event_type = 'chat_message'
+     AND {text} LIKE '%{term}%'
+"#
+            ))
+        };
+        Ok(Self {
+            term: leak(term.to_string()),
+            title: leak(format!("Chat messages containing \"{term}\"")),
+            sqlite: query("events", "payload->>'$.text'"),
+            sqlite_normalized: leak(format!(
+                r#"
+SELECT COUNT(*) AS count
+  FROM events
+  JOIN event_types USING (event_id)
+ WHERE
+     event_type = 'chat_message'
+     AND text LIKE '%{term}%'
+"#
+            )),
+            duck: query("events", "payload->>'$.text'"),
+            duck_typed: query("events", "payload.text"),
+            datafusion: query("events", "json_get(payload, '$.text')"),
+            clickhouse: query("olap.events", "JSONExtractString(payload, 'text')"),
+            postgres: query("events", "payload->>'text'"),
+        })
+    }
+}
+
+impl Benchmark for TextSearch {
+    fn name(&self) -> &'static str {
+        "text_search"
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["SQLite LIKE is case-insensitive for ASCII, the other engines match case"]
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(self.sqlite)
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(self.sqlite_normalized)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(self.duck)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(self.duck_typed)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(self.datafusion)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(self.clickhouse)
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(self.postgres)
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(
+                    col("event_type").eq(lit("chat_message")).and(
+                        col("payload")
+                            .struct_()
+                            .field_by_name("text")
+                            .str()
+                            .contains_literal(self.term),
+                    ),
+                )
+                .select([count().alias("count")]),
+        )
+    }
+}
+
+/// Page loads per hour of the day, a daily traffic pattern. Each engine extracts the hour
+/// with its own function. Small datasets may not span all 24 hours.
+pub struct PageLoadsByHour;
+
+impl Benchmark for PageLoadsByHour {
+    fn name(&self) -> &'static str {
+        "page_loads_by_hour"
+    }
+
+    fn title(&self) -> &'static str {
+        "Page loads by hour of day"
+    }
+
+    fn needs_native_timestamps(&self) -> bool {
+        true
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        if let Some(problem) = at_most_rows(result, 24) {
+            return Some(problem);
+        }
+        result.rows.iter().find_map(|row| match row.first().and_then(number) {
+            Some(hour) if (0.0..24.0).contains(&hour) => None,
+            other => Some(format!("unexpected hour {other:?}")),
+        })
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT CAST(strftime('%H', timestamp) AS INTEGER) AS hour, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY hour
+ ORDER BY hour
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT extract(hour FROM timestamp) AS hour, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY hour
+ ORDER BY hour
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        self.duck_sql()
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        // `date_part` returns a float.
+        Some(
+            r#"
+SELECT CAST(date_part('hour', timestamp) AS BIGINT) AS hour, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY hour
+ ORDER BY hour
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT toHour(timestamp) AS hour, count(*) AS count
+  FROM olap.events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY hour
+ ORDER BY hour
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT EXTRACT(hour FROM timestamp)::int AS hour, COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+ GROUP BY hour
+ ORDER BY hour
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT CAST(strftime('%H', timestamp, 'unixepoch') AS INTEGER) AS hour, COUNT(*) AS count
+  FROM events
+  JOIN event_types USING (event_id)
+ WHERE
+     event_type = 'page_load'
+ GROUP BY hour
+ ORDER BY hour
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([col("timestamp").dt().hour().alias("hour")])
+                .groupby([col("hour")])
+                .agg([count().alias("count")])
+                .sort("hour", Default::default()),
+        )
+    }
+}
+
+/// Crosstab of form submissions by form type and the path of the page they were submitted on.
+/// Every engine extracts `form_type` and `path` from the payload in the same join.
+pub struct FormSubmissionsByTypeAndPath;
+
+impl Benchmark for FormSubmissionsByTypeAndPath {
+    fn name(&self) -> &'static str {
+        "form_submissions_by_type_and_path"
+    }
+
+    fn title(&self) -> &'static str {
+        "Form submissions by form type and page"
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        let known = ["contact-us", "feedback"];
+        result.rows.iter().find_map(|row| match row.first() {
+            Some(CellValue::Text(form_type)) if known.contains(&form_type.as_str()) => None,
+            other => Some(format!("unexpected form type {other:?}")),
+        })
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload->>'$.form_type' AS form_type, e2.payload->>'$.path' AS path, count(*) AS count
+  FROM events e1
+  JOIN events e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+ GROUP BY form_type, path
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload->>'$.form_type' AS form_type, e2.payload->>'$.path' AS path, count(*) AS count
+  FROM events e1
+  JOIN events e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+ GROUP BY form_type, path
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload.form_type AS form_type, e2.payload.path AS path, count(*) AS count
+  FROM events e1
+  JOIN events e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+ GROUP BY form_type, path
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload['form_type'] AS form_type, e2.payload['path'] AS path, count(*) AS count
+  FROM events e1
+  JOIN events e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+ GROUP BY form_type, path
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT JSONExtractString(e1.payload, 'form_type') AS form_type,
+       JSONExtractString(e2.payload, 'path') AS path,
+       count(*) AS count
+  FROM olap.events AS e1
+  JOIN olap.events AS e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+ GROUP BY form_type, path
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT e1.payload->>'form_type' AS form_type, e2.payload->>'path' AS path, count(*) AS count
+  FROM events e1
+  JOIN events e2 ON e1.page_id = e2.page_id
+ WHERE e1.event_type = 'form_submit'
+       AND e2.event_type = 'page_load'
+ GROUP BY 1, 2
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT form_type, path, count(*) AS count
+  FROM events e1
+  JOIN form_types USING (form_id)
+  JOIN events e2 ON e1.page_id = e2.page_id
+  JOIN path_cache ON e2.path_id = path_cache.path_id
+ GROUP BY form_type, path
+ ORDER BY form_type, path
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        let forms_pdf = events
+            .clone()
+            .filter(col("event_type").eq(lit("form_submit")))
+            .select([
+                col("payload")
+                    .struct_()
+                    .field_by_name("form_type")
+                    .alias("form_type"),
+                col("page_id"),
+            ]);
+
+        let paths_pdf = events
+            .filter(col("event_type").eq(lit("page_load")))
+            .select([
+                col("payload").struct_().field_by_name("path").alias("path"),
+                col("page_id"),
+            ]);
+
+        Some(
+            forms_pdf
+                .join(
+                    paths_pdf,
+                    [col("page_id")],
+                    [col("page_id")],
+                    JoinType::Inner,
+                )
+                .groupby([col("form_type"), col("path")])
+                .agg([count().alias("count")])
+                .sort_by_exprs([col("form_type"), col("path")], [false, false], false),
+        )
+    }
+}
+
+const BOUNCE_RATE_SQL: &str = r#"
+WITH session_loads AS (
+  SELECT session_id, COUNT(*) AS loads
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT CAST(SUM(CASE WHEN loads = 1 THEN 1 ELSE 0 END) AS DOUBLE) * 100 / COUNT(*) AS bounce_rate
+  FROM session_loads
+"#;
+
+/// Percentage of sessions with exactly one page load. Counts page loads per session first
+/// and aggregates the counts in a second stage.
+pub struct BounceRate;
+
+impl Benchmark for BounceRate {
+    fn name(&self) -> &'static str {
+        "bounce_rate"
+    }
+
+    fn title(&self) -> &'static str {
+        "Bounce rate (sessions with a single page load)"
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        result.rows.iter().find_map(|row| match row.first().and_then(number) {
+            Some(rate) if (0.0..=100.0).contains(&rate) => None,
+            other => Some(format!("expected a percentage between 0 and 100, got {other:?}")),
+        })
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(BOUNCE_RATE_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(BOUNCE_RATE_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(BOUNCE_RATE_SQL)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(BOUNCE_RATE_SQL)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH session_loads AS (
+  SELECT session_id, count(*) AS loads
+    FROM olap.events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT countIf(loads = 1) * 100 / count(*) AS bounce_rate FROM session_loads
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH session_loads AS (
+  SELECT session_id, COUNT(*) AS loads
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT COUNT(*) FILTER (WHERE loads = 1)::float8 * 100 / COUNT(*) AS bounce_rate
+  FROM session_loads
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH session_loads AS (
+  SELECT session_id, COUNT(*) AS loads
+    FROM events
+    JOIN event_types USING (event_id)
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT CAST(SUM(CASE WHEN loads = 1 THEN 1 ELSE 0 END) AS DOUBLE) * 100 / COUNT(*) AS bounce_rate
+  FROM session_loads
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .groupby([col("session_id")])
+                .agg([count().alias("loads")])
+                .select([(col("loads")
+                    .eq(lit(1))
+                    .cast(DataType::Float64)
+                    .mean()
+                    * lit(100.0))
+                .alias("bounce_rate")]),
+        )
+    }
+}
+
+/// Same as [`AvgFeedbackScore`] over the `score` field of `--typed-payload scored`.
+pub struct AvgFeedbackScoreTyped;
+
+impl Benchmark for AvgFeedbackScoreTyped {
+    fn name(&self) -> &'static str {
+        "avg_feedback_score_typed"
+    }
+
+    fn title(&self) -> &'static str {
+        "Average feedback score (typed score)"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["Requires data generated with --typed-payload scored"]
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        AvgFeedbackScore.check(result)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(payload.score) AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND payload.form_type = 'feedback'
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT AVG(payload['score']) AS average
+  FROM events
+ WHERE
+     event_type = 'form_submit'
+     AND payload['form_type'] = 'feedback'
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(
+                    col("event_type").eq(lit("form_submit")).and(
+                        col("payload")
+                            .struct_()
+                            .field_by_name("form_type")
+                            .eq(lit("feedback")),
+                    ),
+                )
+                .select([col("payload").struct_().field_by_name("score").alias("score")])
+                .select([avg("score")]),
+        )
+    }
+}
+
+const HEAVIEST_SESSIONS_SQL: &str = r#"
+SELECT session_id, COUNT(*) AS events
+  FROM events
+ GROUP BY session_id
+ ORDER BY events DESC, session_id
+ LIMIT 10
+"#;
+
+/// Hash aggregation over the high-cardinality `session_id` instead of a handful of event types.
+pub struct HeaviestSessions;
+
+impl Benchmark for HeaviestSessions {
+    fn name(&self) -> &'static str {
+        "heaviest_sessions"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sessions with the most events"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["Ties are ordered by session id so every engine returns the same sessions"]
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        at_most_rows(result, 10)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(HEAVIEST_SESSIONS_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(HEAVIEST_SESSIONS_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(HEAVIEST_SESSIONS_SQL)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(HEAVIEST_SESSIONS_SQL)
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT session_id, count(*) AS events
+  FROM olap.events
+ GROUP BY session_id
+ ORDER BY events DESC, session_id
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(HEAVIEST_SESSIONS_SQL)
+    }
+
+    /// Sessions are grouped by the 16 byte BLOB, only the top 10 are formatted as the
+    /// hyphenated text UUID the other engines return.
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH heaviest AS (
+  SELECT session_id, COUNT(*) AS events
+    FROM events
+   GROUP BY session_id
+   ORDER BY events DESC, session_id
+   LIMIT 10
+), hex_ids AS (
+  SELECT lower(hex(session_id)) AS h, events FROM heaviest
+)
+SELECT
+    substr(h, 1, 8) || '-' || substr(h, 9, 4) || '-' || substr(h, 13, 4) || '-'
+      || substr(h, 17, 4) || '-' || substr(h, 21) AS session_id,
+    events
+  FROM hex_ids
+ ORDER BY events DESC, session_id
+"#,
+        )
+    }
+
+    fn polars_sql(&self) -> Option<&'static str> {
+        Some(HEAVIEST_SESSIONS_SQL)
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .groupby([col("session_id")])
+                .agg([count().alias("events")])
+                .sort_by_exprs([col("events"), col("session_id")], [true, false], false)
+                .limit(10),
+        )
+    }
+}
+
+const CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL: &str = r#"
+WITH page_events AS (
+  SELECT session_id, page_id, COUNT(*) AS events
+    FROM events
+   GROUP BY session_id, page_id
+)
+SELECT COUNT(*) AS pages, COUNT(DISTINCT session_id) AS sessions
+  FROM page_events p
+ WHERE p.events > (
+     SELECT AVG(s.events)
+       FROM page_events s
+      WHERE s.session_id = p.session_id
+ )
+"#;
+
+/// Pages with more events than the average page of their session. The average is a correlated
+/// subquery on purpose, engines that can't decorrelate it run it once per page.
+pub struct CorrelatedPagesAboveSessionAverage;
+
+impl Benchmark for CorrelatedPagesAboveSessionAverage {
+    fn name(&self) -> &'static str {
+        "correlated_pages_above_session_average"
+    }
+
+    fn title(&self) -> &'static str {
+        "Pages with more events than their session's average (correlated subquery)"
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &[
+            "SQL: correlated scalar subquery per page",
+            "Polars: session average as a window expression",
+        ]
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    /// ClickHouse doesn't support correlated subqueries, kept so that the error is reported.
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH page_events AS (
+  SELECT session_id, page_id, count(*) AS events
+    FROM olap.events
+   GROUP BY session_id, page_id
+)
+SELECT count(*) AS pages, count(DISTINCT session_id) AS sessions
+  FROM page_events p
+ WHERE p.events > (
+     SELECT avg(s.events)
+       FROM page_events s
+      WHERE s.session_id = p.session_id
+ )
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    /// Ids are BLOBs in the normalized schema, the query doesn't depend on their type.
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    fn sqlite_msgpack_sql(&self) -> Option<&'static str> {
+        Some(CORRELATED_PAGES_ABOVE_SESSION_AVERAGE_SQL)
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .groupby([col("session_id"), col("page_id")])
+                .agg([count().alias("events")])
+                .filter(col("events").gt(col("events").mean().over([col("session_id")])))
+                .select([
+                    col("page_id").count().alias("pages"),
+                    col("session_id").n_unique().alias("sessions"),
+                ]),
+        )
+    }
+}
+
+/// Average of the last 7 days of [`PageLoadsPerDay`] with a `ROWS` window frame. The frame
+/// counts rows, not days, so days without page loads are added from a date spine where the
+/// engine can generate one.
+pub struct PageLoadsMovingAverage;
+
+impl Benchmark for PageLoadsMovingAverage {
+    fn name(&self) -> &'static str {
+        "page_loads_moving_average"
+    }
+
+    fn title(&self) -> &'static str {
+        "7-day moving average of page loads"
+    }
+
+    fn needs_native_timestamps(&self) -> bool {
+        true
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &[
+            "Gaps filled with 0: SQLite, DuckDB, Postgres (date spine), ClickHouse (WITH FILL)",
+            "Gaps skipped: DataFusion, Polars (7 days with page loads)",
+        ]
+    }
+
+    fn check(&self, result: &QueryResult) -> Option<String> {
+        at_most_rows(result, 10)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH RECURSIVE per_day AS (
+  SELECT date(timestamp) AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+), days(date) AS (
+  SELECT MIN(date) FROM per_day
+  UNION ALL
+  SELECT date(date, '+1 day') FROM days WHERE date < (SELECT MAX(date) FROM per_day)
+)
+SELECT
+    days.date AS date,
+    COALESCE(per_day.count, 0) AS count,
+    AVG(COALESCE(per_day.count, 0))
+      OVER (ORDER BY days.date ROWS BETWEEN 6 PRECEDING AND CURRENT ROW) AS moving_average
+  FROM days
+  LEFT JOIN per_day ON per_day.date = days.date
+ ORDER BY days.date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH RECURSIVE per_day AS (
+  SELECT CAST(timestamp AS DATE) AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+), days AS (
+  SELECT MIN(date) AS date FROM per_day
+  UNION ALL
+  SELECT date + 1 FROM days WHERE date < (SELECT MAX(date) FROM per_day)
+)
+SELECT
+    strftime(days.date, '%Y-%m-%d') AS date,
+    COALESCE(per_day.count, 0) AS count,
+    AVG(COALESCE(per_day.count, 0))
+      OVER (ORDER BY days.date ROWS BETWEEN 6 PRECEDING AND CURRENT ROW) AS moving_average
+  FROM days
+  LEFT JOIN per_day ON per_day.date = days.date
+ ORDER BY days.date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        self.duck_sql()
+    }
+
+    /// DataFusion has neither recursive CTEs nor `generate_series` to build a date spine.
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT date_trunc('day', timestamp) AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY date
+)
+SELECT
+    date,
+    count,
+    AVG(count) OVER (ORDER BY date ROWS BETWEEN 6 PRECEDING AND CURRENT ROW) AS moving_average
+  FROM per_day
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    /// `WITH FILL` adds the missing days with a zero count before the window is applied.
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+SELECT
+    date,
+    count,
+    avg(count) OVER (ORDER BY date ROWS BETWEEN 6 PRECEDING AND CURRENT ROW) AS moving_average
+  FROM (
+    SELECT toDate(timestamp) AS date, count(*) AS count
+      FROM olap.events
+     WHERE event_type = 'page_load'
+     GROUP BY date
+     ORDER BY date WITH FILL
+  )
+ ORDER BY date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH per_day AS (
+  SELECT timestamp::date AS date, COUNT(*) AS count
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY 1
+), days AS (
+  SELECT generate_series(MIN(date), MAX(date), interval '1 day')::date AS date
+    FROM per_day
+)
+SELECT
+    to_char(days.date, 'YYYY-MM-DD') AS date,
+    COALESCE(per_day.count, 0) AS count,
+    AVG(COALESCE(per_day.count, 0))
+      OVER (ORDER BY days.date ROWS BETWEEN 6 PRECEDING AND CURRENT ROW)::float8 AS moving_average
+  FROM days
+  LEFT JOIN per_day ON per_day.date = days.date
+ ORDER BY days.date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH RECURSIVE per_day AS (
+  SELECT date(timestamp, 'unixepoch') AS date, COUNT(*) AS count
+    FROM events
+    JOIN event_types USING (event_id)
+   WHERE event_type = 'page_load'
+   GROUP BY date
+), days(date) AS (
+  SELECT MIN(date) FROM per_day
+  UNION ALL
+  SELECT date(date, '+1 day') FROM days WHERE date < (SELECT MAX(date) FROM per_day)
+)
+SELECT
+    days.date AS date,
+    COALESCE(per_day.count, 0) AS count,
+    AVG(COALESCE(per_day.count, 0))
+      OVER (ORDER BY days.date ROWS BETWEEN 6 PRECEDING AND CURRENT ROW) AS moving_average
+  FROM days
+  LEFT JOIN per_day ON per_day.date = days.date
+ ORDER BY days.date
+ LIMIT 10
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([col("timestamp").dt().date().alias("date")])
+                .groupby([col("date")])
+                .agg([count().alias("count")])
+                .sort("date", Default::default())
+                .with_column(
+                    col("count")
+                        .cast(DataType::Float64)
+                        .rolling_mean(RollingOptions {
+                            window_size: polars::prelude::Duration::new(7),
+                            min_periods: 1,
+                            ..Default::default()
+                        })
+                        .alias("moving_average"),
+                )
+                .limit(10),
+        )
+    }
+}
+
+const DISTINCT_PATHS_PER_SESSION_SQL: &str = r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(DISTINCT payload->>'$.path') AS paths
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT paths, COUNT(*) AS sessions
+  FROM sessions
+ GROUP BY paths
+ ORDER BY paths
+"#;
+
+/// How many distinct paths each session loaded, bucketed into a histogram. Combines a distinct
+/// count per session with a second aggregation over the counts.
+pub struct DistinctPathsPerSession;
+
+impl Benchmark for DistinctPathsPerSession {
+    fn name(&self) -> &'static str {
+        "distinct_paths_per_session"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sessions by number of distinct paths visited"
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(DISTINCT_PATHS_PER_SESSION_SQL)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(DISTINCT_PATHS_PER_SESSION_SQL)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(DISTINCT payload.path) AS paths
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT paths, COUNT(*) AS sessions
+  FROM sessions
+ GROUP BY paths
+ ORDER BY paths
+"#,
+        )
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(DISTINCT json_get(payload, '$.path')) AS paths
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT paths, COUNT(*) AS sessions
+  FROM sessions
+ GROUP BY paths
+ ORDER BY paths
+"#,
+        )
+    }
+
+    fn clickhouse_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, uniqExact(JSONExtractString(payload, 'path')) AS paths
+    FROM olap.events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT paths, count(*) AS sessions
+  FROM sessions
+ GROUP BY paths
+ ORDER BY paths
+"#,
+        )
+    }
+
+    fn postgres_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(DISTINCT payload->>'path') AS paths
+    FROM events
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT paths, COUNT(*) AS sessions
+  FROM sessions
+ GROUP BY paths
+ ORDER BY paths
+"#,
+        )
+    }
+
+    fn sqlite_normalized_sql(&self) -> Option<&'static str> {
+        Some(
+            r#"
+WITH sessions AS (
+  SELECT session_id, COUNT(DISTINCT path) AS paths
+    FROM events
+    JOIN event_types USING (event_id)
+    JOIN path_cache USING (path_id)
+   WHERE event_type = 'page_load'
+   GROUP BY session_id
+)
+SELECT paths, COUNT(*) AS sessions
+  FROM sessions
+ GROUP BY paths
+ ORDER BY paths
+"#,
+        )
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(col("event_type").eq(lit("page_load")))
+                .select([
+                    col("session_id"),
+                    col("payload").struct_().field_by_name("path").alias("path"),
+                ])
+                .groupby([col("session_id")])
+                .agg([col("path").n_unique().alias("paths")])
+                .groupby([col("paths")])
+                .agg([count().alias("sessions")])
+                .sort("paths", Default::default()),
+        )
+    }
+}
+
+/// Page loads of a single day of the day-partitioned Parquet dataset. DataFusion filters on
+/// the `date` partition column and only reads that day's directory, the other engines scan
+/// every file and filter on `timestamp`. Only runs with `queries --parquet-partitioned`.
+///
+/// The day is only known at runtime, so the SQL is built once and leaked like in
+/// [`TextSearch`].
+pub struct PageLoadsOnDay {
+    day: &'static str,
+    title: &'static str,
+    sqlite: &'static str,
+    duck: &'static str,
+    datafusion: Option<&'static str>,
+}
+
+impl PageLoadsOnDay {
+    /// `datafusion_partitioned` is whether DataFusion has the `date` partition column, i.e.
+    /// reads the partitioned Parquet dataset rather than SQLite.
+    pub fn new(day: NaiveDate, datafusion_partitioned: bool) -> Self {
+        let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+        let query = |filter: String| {
+            leak(format!(
+                r#"
+SELECT COUNT(*) AS count
+  FROM events
+ WHERE
+     event_type = 'page_load'
+     AND {filter}
+"#
+            ))
+        };
+        Self {
+            day: leak(day.to_string()),
+            title: leak(format!("Page loads on {day}")),
+            sqlite: query(format!("date(timestamp) = '{day}'")),
+            duck: query(format!("CAST(timestamp AS DATE) = DATE '{day}'")),
+            datafusion: datafusion_partitioned.then(|| query(format!("date = '{day}'"))),
+        }
+    }
+}
+
+impl Benchmark for PageLoadsOnDay {
+    fn name(&self) -> &'static str {
+        "page_loads_on_day"
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn needs_native_timestamps(&self) -> bool {
+        true
+    }
+
+    fn notes(&self) -> &'static [&'static str] {
+        &["DataFusion prunes the other days' partitions, the other engines scan all of them"]
+    }
+
+    fn expected_rows(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn sqlite_sql(&self) -> Option<&'static str> {
+        Some(self.sqlite)
+    }
+
+    fn duck_sql(&self) -> Option<&'static str> {
+        Some(self.duck)
+    }
+
+    fn duck_typed_sql(&self) -> Option<&'static str> {
+        Some(self.duck)
+    }
+
+    fn datafusion_sql(&self) -> Option<&'static str> {
+        self.datafusion
+    }
+
+    fn polars(&self, events: LazyFrame) -> Option<LazyFrame> {
+        Some(
+            events
+                .filter(
+                    col("event_type").eq(lit("page_load")).and(
+                        col("timestamp").dt().strftime("%Y-%m-%d").eq(lit(self.day)),
+                    ),
+                )
+                .select([count().alias("count")]),
+        )
+    }
+}
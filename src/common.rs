@@ -1,130 +1,2534 @@
-use std::time::Instant;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    env,
+    fs::{self, File, OpenOptions},
+    fmt,
+    future::Future,
+    io::{IsTerminal, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{self, AtomicU8},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
-use datafusion::prelude::SessionContext;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use datafusion::{
+    arrow::{
+        array::{
+            as_list_array, as_primitive_array, as_string_array, as_struct_array, Array, ArrayRef,
+            Float64Array, Int64Array, StringArray, TimestampMicrosecondArray,
+        },
+        compute::cast,
+        datatypes::{DataType, Field, Float64Type, Int32Type, Int64Type, Schema, TimeUnit},
+        record_batch::RecordBatch,
+        util::{
+            display::array_value_to_string,
+            pretty::pretty_format_batches,
+        },
+    },
+    error::DataFusionError,
+    logical_expr::{
+        ColumnarValue, ReturnTypeFunction, ScalarFunctionImplementation, ScalarUDF, Signature,
+        Volatility,
+    },
+    prelude::SessionContext,
+    scalar::ScalarValue,
+};
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, CellAlignment, ContentArrangement, Table,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use polars::{
+    prelude::{AnyValue, DataFrame, LazyFrame},
+    sql::SQLContext,
+};
+use tracing_subscriber::EnvFilter;
 
-pub fn exec_sqlite(conn: &rusqlite::Connection, query: &str) -> Result<()> {
+/// `print!` that goes to the [`capture_output`] buffer of the current thread if there is one.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write_out(format_args!($($arg)*))
+    };
+}
+
+/// `println!` counterpart of [`out!`].
+macro_rules! outln {
+    () => {
+        out!("\n")
+    };
+    ($($arg:tt)*) => {{
+        out!($($arg)*);
+        out!("\n");
+    }};
+}
+
+thread_local! {
+    static CAPTURED: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Write to stdout, or to the buffer while [`capture_output`] runs on this thread.
+pub fn write_out(args: fmt::Arguments) {
+    CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(buf) => {
+            let _ = fmt::Write::write_fmt(buf, args);
+        }
+        None => {
+            let _ = std::io::stdout().lock().write_fmt(args);
+        }
+    });
+}
+
+/// Run `f` and return what it printed through the `exec_*` functions instead of printing it,
+/// so that engines running on different threads don't interleave their output.
+pub fn capture_output<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let previous = CAPTURED.with(|captured| captured.replace(Some(String::new())));
+    let result = f();
+    let output = CAPTURED.with(|captured| captured.replace(previous));
+    (result, output.unwrap_or_default())
+}
+
+/// Query engines under comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Engine {
+    Sqlite,
+    /// Normalized SQLite schema from `gen_data_normalized`.
+    SqliteNormalized,
+    Duck,
+    DuckTyped,
+    /// In-memory DuckDB scanning the same parquet file as Polars and DataFusion.
+    DuckParquet,
+    Polars,
+    /// Polars SQL interface over the same parquet file as `Polars`.
+    #[value(name = "polars-sql")]
+    PolarsSql,
+    #[value(name = "datafusion")]
+    DataFusion,
+    /// Embedded ClickHouse (chDB), requires `clickhouse` cargo feature.
+    #[value(name = "clickhouse")]
+    ClickHouse,
+    /// Postgres server from `DATABASE_URL`, requires `postgres` cargo feature.
+    Postgres,
+    /// SQLite with a MessagePack payload, requires `msgpack` cargo feature.
+    SqliteMsgpack,
+}
+
+impl Engine {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Engine::Sqlite => "SQLite",
+            Engine::SqliteNormalized => "SQLite (normalized)",
+            Engine::Duck => "DuckDB",
+            Engine::DuckTyped => "DuckDB (Typed)",
+            Engine::DuckParquet => "DuckDB (Parquet)",
+            Engine::Polars => "Polars",
+            Engine::PolarsSql => "Polars (SQL)",
+            Engine::DataFusion => "DataFusion",
+            Engine::ClickHouse => "ClickHouse",
+            Engine::Postgres => "Postgres",
+            Engine::SqliteMsgpack => "SQLite (MessagePack)",
+        }
+    }
+}
+
+/// How the generators store the `timestamp` column.
+///
+/// Timestamps are always UTC. By default each store uses its native type, pass
+/// `--timestamps epoch-millis` to store milliseconds since the Unix epoch everywhere instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimestampFormat {
+    /// `TIMESTAMP` columns, SQLite stores `%F %T%.f%:z` text.
+    #[default]
+    Native,
+    /// Milliseconds since the Unix epoch in a 64-bit integer column.
+    EpochMillis,
+}
+
+impl TimestampFormat {
+    /// SQL type of the `timestamp` column for the engine.
+    pub fn column_type(self, engine: Engine) -> &'static str {
+        match (self, engine) {
+            (TimestampFormat::EpochMillis, Engine::ClickHouse) => "Int64",
+            (TimestampFormat::EpochMillis, _) => "BIGINT",
+            (TimestampFormat::Native, Engine::Sqlite) => "TEXT",
+            (TimestampFormat::Native, Engine::ClickHouse) => "DateTime64(6)",
+            (TimestampFormat::Native, _) => "TIMESTAMP",
+        }
+    }
+}
+
+/// A timestamp converted for a specific engine, see [`format_timestamp_for`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimestampValue {
+    DateTime(DateTime<Utc>),
+    Text(String),
+    EpochMillis(i64),
+}
+
+/// Convert `ts` to the value stored in the engine's `timestamp` column.
+pub fn format_timestamp_for(
+    engine: Engine,
+    format: TimestampFormat,
+    ts: DateTime<Utc>,
+) -> TimestampValue {
+    match (format, engine) {
+        (TimestampFormat::EpochMillis, _) => TimestampValue::EpochMillis(ts.timestamp_millis()),
+        (TimestampFormat::Native, Engine::ClickHouse) => {
+            TimestampValue::Text(ts.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+        }
+        (TimestampFormat::Native, _) => TimestampValue::DateTime(ts),
+    }
+}
+
+impl TimestampValue {
+    /// Value for JSON based formats (NDJSON, ClickHouse JSONEachRow).
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            TimestampValue::DateTime(ts) => ts.to_rfc3339_opts(SecondsFormat::Micros, true).into(),
+            TimestampValue::Text(text) => text.as_str().into(),
+            TimestampValue::EpochMillis(ms) => (*ms).into(),
+        }
+    }
+}
+
+impl rusqlite::ToSql for TimestampValue {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        match self {
+            TimestampValue::DateTime(ts) => rusqlite::ToSql::to_sql(ts),
+            TimestampValue::Text(text) => rusqlite::ToSql::to_sql(text),
+            TimestampValue::EpochMillis(ms) => rusqlite::ToSql::to_sql(ms),
+        }
+    }
+}
+
+impl duckdb::ToSql for TimestampValue {
+    fn to_sql(&self) -> duckdb::Result<duckdb::types::ToSqlOutput<'_>> {
+        match self {
+            TimestampValue::DateTime(ts) => duckdb::ToSql::to_sql(ts),
+            TimestampValue::Text(text) => duckdb::ToSql::to_sql(text),
+            TimestampValue::EpochMillis(ms) => duckdb::ToSql::to_sql(ms),
+        }
+    }
+}
+
+/// Rows returned by a query together with their column names.
+#[derive(Clone)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<CellValue>>,
+}
+
+/// Engine independent representation of a single value.
+///
+/// Integers of every width end up as `Int`, timestamps and dates as text in
+/// the `%Y-%m-%d %H:%M:%S` / `%Y-%m-%d` formats so that the same logical value
+/// looks the same no matter which engine produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    /// Binary data, only the length is kept.
+    Bytes(usize),
+    /// Column that couldn't be read, printed as an empty cell. Not an engine's value, so
+    /// results containing it are left out of [`compare_results`].
+    Unreadable,
+}
+
+/// Log to stderr, `RUST_LOG` overrides the default filter.
+pub fn init_tracing() {
+    if env::var_os("RUST_LOG").is_none() {
+        env::set_var("RUST_LOG", "info,compare-olap-rust=debug");
+    }
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+}
+
+/// Directory holding the generated databases and files.
+#[derive(Clone)]
+pub struct DataDir(PathBuf);
+
+impl DataDir {
+    /// Create the directory if it doesn't exist.
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create data dir {}", path.display()))?;
+        Ok(Self(path))
+    }
+
+    /// Path of `name` inside the directory.
+    pub fn file(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+/// Progress of the generated sessions with the insert rate and ETA.
+///
+/// The bar is hidden when disabled or when stdout isn't a terminal, progress is logged
+/// every 10k sessions instead.
+pub struct SessionProgress {
+    bar: ProgressBar,
+    total: u64,
+    sessions: u64,
+    events: u64,
+    started: Instant,
+}
+
+impl SessionProgress {
+    pub fn new(total: u64, enabled: bool) -> Self {
+        let bar = if enabled && std::io::stdout().is_terminal() {
+            ProgressBar::new(total)
+        } else {
+            ProgressBar::hidden()
+        };
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{elapsed_precise} [{wide_bar}] {pos}/{len} sessions, {msg}, ETA {eta}",
+            )
+            .unwrap(),
+        );
+        Self {
+            bar,
+            total,
+            sessions: 0,
+            events: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record a session with `events` rows.
+    pub fn inc(&mut self, events: usize) {
+        if self.bar.is_hidden() && self.sessions % 10000 == 0 {
+            tracing::info!("#{}/{}: Inserting session", self.sessions, self.total);
+        }
+
+        self.sessions += 1;
+        self.events += events as u64;
+        self.bar.inc(1);
+        if self.sessions % 100 == 0 {
+            let rate = self.events as f64 / self.started.elapsed().as_secs_f64();
+            self.bar.set_message(format!("{rate:.0} rows/sec"));
+        }
+    }
+
+    pub fn finish(self) {
+        self.bar.finish_and_clear();
+        let elapsed = self.started.elapsed();
+        tracing::info!(
+            "Generated {} sessions with {} rows in {elapsed:?}",
+            self.sessions,
+            self.events
+        );
+    }
+}
+
+/// Whether the SQLite database has a table with this name.
+pub fn sqlite_table_exists(conn: &rusqlite::Connection, table: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Names of the indexes created on `table`, automatic indexes of constraints are left out.
+pub fn sqlite_indexes(conn: &rusqlite::Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL \
+         ORDER BY name",
+    )?;
+    let names = stmt.query_map([table], |row| row.get(0))?;
+    Ok(names.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Whether the DuckDB database has a table with this name.
+pub fn duck_table_exists(conn: &duckdb::Connection, table: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM information_schema.tables WHERE table_name = ?",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// How `exec_*` functions print query results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable table with columns fitted to their values and colored timings.
+    #[default]
+    Table,
+    /// Uncolored ASCII table, DataFusion and Polars print results in their own format.
+    Plain,
+    /// Header line followed by comma separated rows.
+    Csv,
+    /// One JSON object per row.
+    Json,
+    /// GitHub-flavored Markdown table.
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Read the format from `COMPARE_OLAP_FORMAT` env var. Defaults to `Table`.
+    pub fn from_env() -> Result<Self> {
+        let Ok(value) = env::var("COMPARE_OLAP_FORMAT") else {
+            return Ok(Self::default());
+        };
+
+        match value.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "plain" => Ok(Self::Plain),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "markdown" | "md" => Ok(Self::Markdown),
+            other => bail!(
+                "Unknown COMPARE_OLAP_FORMAT={other} (expected table, plain, csv, json or markdown)"
+            ),
+        }
+    }
+}
+
+/// Appends one JSON object per executed query to the file in `BENCH_OUTPUT` env var.
+///
+/// Cloned recorders share the same file handle so they can be moved into worker threads.
+#[derive(Clone, Default)]
+pub struct BenchRecorder {
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl BenchRecorder {
+    pub fn from_env() -> Result<Self> {
+        let Some(path) = env::var_os("BENCH_OUTPUT") else {
+            return Ok(Self::default());
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open BENCH_OUTPUT={path:?}"))?;
+        Ok(Self {
+            file: Some(Arc::new(Mutex::new(file))),
+        })
+    }
+
+    /// `millis` is the median, `iteration_micros` lists every timed run in execution order.
+    pub fn record(
+        &self,
+        engine: &str,
+        query_name: &str,
+        timings: &Timings,
+        rows: usize,
+    ) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let iteration_micros: Vec<u64> =
+            timings.durations().iter().map(|d| d.as_micros() as u64).collect();
+        let line = serde_json::json!({
+            "engine": engine,
+            "query_name": query_name,
+            "millis": timings.median().as_millis() as u64,
+            "iteration_micros": iteration_micros,
+            "rows": rows,
+        });
+        Self::write_line(file, line)
+    }
+
+    /// Record a failed query with its [`ErrorCategory`] under `error` and the full message.
+    pub fn record_error(
+        &self,
+        engine: &str,
+        query_name: &str,
+        category: ErrorCategory,
+        message: &str,
+    ) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let line = serde_json::json!({
+            "engine": engine,
+            "query_name": query_name,
+            "error": category.name(),
+            "message": message,
+        });
+        Self::write_line(file, line)
+    }
+
+    /// Record a `{"versions": {...}}` line with the engine versions of this run.
+    pub fn record_versions(&self, versions: &[(&str, String)]) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let versions: serde_json::Map<_, _> = versions
+            .iter()
+            .map(|(engine, version)| (engine.to_string(), version.clone().into()))
+            .collect();
+        Self::write_line(file, serde_json::json!({ "versions": versions }))
+    }
+
+    fn write_line(file: &Mutex<File>, line: serde_json::Value) -> Result<()> {
+        // Write the whole line while holding the lock so lines from different threads never interleave.
+        let mut file = file.lock().unwrap();
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+}
+
+/// Per engine totals printed at the end of a run.
+///
+/// Cloned summaries share the same state, like [`BenchRecorder`].
+#[derive(Clone, Default)]
+pub struct BenchSummary {
+    engines: Arc<Mutex<Vec<EngineSummary>>>,
+}
+
+struct EngineSummary {
+    label: String,
+    /// Sum of median durations of each query.
+    total: Duration,
+    /// Median duration of the latest query.
+    last: Duration,
+    queries: usize,
+    errors: BTreeMap<ErrorCategory, usize>,
+    timeouts: usize,
+}
+
+impl BenchSummary {
+    fn update(&self, label: &str, f: impl FnOnce(&mut EngineSummary)) {
+        let mut engines = self.engines.lock().unwrap();
+        match engines.iter_mut().find(|e| e.label == label) {
+            Some(engine) => f(engine),
+            None => {
+                let mut engine = EngineSummary {
+                    label: label.to_string(),
+                    total: Duration::ZERO,
+                    last: Duration::ZERO,
+                    queries: 0,
+                    errors: BTreeMap::new(),
+                    timeouts: 0,
+                };
+                f(&mut engine);
+                engines.push(engine);
+            }
+        }
+    }
+
+    pub fn add(&self, label: &str, duration: Duration) {
+        self.update(label, |e| {
+            e.total += duration;
+            e.last = duration;
+            e.queries += 1;
+        });
+    }
+
+    /// Median duration of the latest successful query of the engine.
+    pub fn last(&self, label: &str) -> Option<Duration> {
+        let engines = self.engines.lock().unwrap();
+        engines.iter().find(|e| e.label == label && e.queries > 0).map(|e| e.last)
+    }
+
+    pub fn add_error(&self, label: &str, category: ErrorCategory) {
+        self.update(label, |e| *e.errors.entry(category).or_default() += 1);
+    }
+
+    pub fn add_timeout(&self, label: &str) {
+        self.update(label, |e| e.timeouts += 1);
+    }
+
+    /// Print one line per engine, fastest first.
+    pub fn print(&self) {
+        let mut engines = self.engines.lock().unwrap();
+        engines.sort_by_key(|e| e.total);
+
+        outln!("Summary:");
+        for e in engines.iter() {
+            out!(
+                "{} total: {:.1}s across {} queries",
+                e.label,
+                e.total.as_secs_f64(),
+                e.queries
+            );
+            if !e.errors.is_empty() {
+                let failed: usize = e.errors.values().sum();
+                let categories: Vec<_> = e
+                    .errors
+                    .iter()
+                    .map(|(category, count)| category.describe(*count))
+                    .collect();
+                out!(", {failed} failed ({})", categories.join(", "));
+            }
+            if e.timeouts > 0 {
+                out!(", {} timed out", e.timeouts);
+            }
+            outln!();
+        }
+    }
+}
+
+/// Kind of a failed query, to tell dialect differences apart from engines failing to run
+/// a query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorCategory {
+    /// The engine couldn't parse the SQL.
+    Parse,
+    /// Parsed but uses a function, column or feature the engine doesn't have.
+    Unsupported,
+    /// Failed while running, e.g. invalid data or out of memory.
+    Runtime,
+}
+
+/// Lowercase message fragments of parse errors from DuckDB, SQLite, Postgres and ClickHouse.
+const PARSE_ERRORS: &[&str] = &["parser error", "syntax error"];
+
+/// Lowercase message fragments of errors about missing functions or features.
+const UNSUPPORTED_ERRORS: &[&str] = &[
+    "not implemented",
+    "not supported",
+    "unsupported",
+    "no such function",
+    "unknown function",
+    "does not exist",
+    "catalog error",
+    "binder error",
+];
+
+impl ErrorCategory {
+    /// Best guess from the backend error. DataFusion errors are typed, the other engines are
+    /// classified by their messages.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            match cause.downcast_ref::<DataFusionError>() {
+                Some(DataFusionError::SQL(_)) => return Self::Parse,
+                Some(DataFusionError::NotImplemented(_) | DataFusionError::Plan(_)) => {
+                    return Self::Unsupported
+                }
+                _ => {}
+            }
+        }
+        let message = format!("{err:#}").to_lowercase();
+        if PARSE_ERRORS.iter().any(|m| message.contains(m)) {
+            Self::Parse
+        } else if UNSUPPORTED_ERRORS.iter().any(|m| message.contains(m)) {
+            Self::Unsupported
+        } else {
+            Self::Runtime
+        }
+    }
+
+    /// Value of `error` in `BENCH_OUTPUT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCategory::Parse => "parse",
+            ErrorCategory::Unsupported => "unsupported",
+            ErrorCategory::Runtime => "runtime",
+        }
+    }
+
+    /// E.g. "2 parse errors".
+    fn describe(self, count: usize) -> String {
+        let s = if count == 1 { "" } else { "s" };
+        match self {
+            ErrorCategory::Parse => format!("{count} parse error{s}"),
+            ErrorCategory::Unsupported => format!("{count} unsupported"),
+            ErrorCategory::Runtime => format!("{count} runtime error{s}"),
+        }
+    }
+}
+
+const DEFAULT_COLUMN_WIDTH: usize = 20;
+
+pub const DEFAULT_MAX_PRINT_ROWS: usize = 50;
+
+/// Settings shared by all `exec_*` functions.
+#[derive(Clone)]
+pub struct ExecOptions {
+    pub format: OutputFormat,
+    pub recorder: BenchRecorder,
+    /// How many times each query is executed. Read from `BENCH_ITERS` env var.
+    pub iterations: usize,
+    /// How many untimed runs precede the timed ones. Read from `BENCH_WARMUP` env var.
+    pub warmup: usize,
+    /// Run Polars queries with the streaming engine where possible.
+    pub polars_streaming: bool,
+    pub summary: BenchSummary,
+    /// Report peak RSS of each query, Linux only.
+    pub measure_memory: bool,
+    /// Table column width, longer values are truncated. `None` fits columns to their values.
+    /// Read from `COMPARE_OLAP_WIDTH` env var.
+    pub column_width: Option<usize>,
+    /// Abandon a query run that takes longer than this and return [`QueryTimedOut`].
+    pub timeout: Option<Duration>,
+    /// Print at most this many rows of each result in table formats, all rows are still
+    /// collected and timed.
+    pub max_print_rows: Option<usize>,
+    /// Print DuckDB's `EXPLAIN ANALYZE` output after each DuckDB query. The plan is captured in
+    /// an extra untimed run.
+    pub duck_explain: bool,
+    /// Directory from `PLAN_OUTPUT` env var where `queries` saves each engine's query plan.
+    pub plan_output: Option<PathBuf>,
+    /// Print only the column names and timings of each result, no rows. Set by
+    /// `COMPARE_OLAP_QUIET` env var.
+    pub quiet: bool,
+}
+
+impl Default for ExecOptions {
+    fn default() -> Self {
+        Self {
+            format: Default::default(),
+            recorder: Default::default(),
+            summary: Default::default(),
+            iterations: 1,
+            warmup: 0,
+            polars_streaming: false,
+            measure_memory: false,
+            column_width: Some(DEFAULT_COLUMN_WIDTH),
+            timeout: None,
+            max_print_rows: Some(DEFAULT_MAX_PRINT_ROWS),
+            duck_explain: false,
+            plan_output: None,
+            quiet: false,
+        }
+    }
+}
+
+impl ExecOptions {
+    pub fn from_env() -> Result<Self> {
+        let iterations = match env::var("BENCH_ITERS") {
+            Ok(v) => match v.parse() {
+                Ok(n) if n > 0 => n,
+                _ => bail!("BENCH_ITERS must be a positive integer, got {v}"),
+            },
+            Err(_) => 1,
+        };
+        // Without an explicit warmup discard the first run when there are enough iterations.
+        let warmup = match env::var("BENCH_WARMUP") {
+            Ok(v) => match v.parse() {
+                Ok(n) => n,
+                _ => bail!("BENCH_WARMUP must be a non-negative integer, got {v}"),
+            },
+            Err(_) if iterations > 3 => 1,
+            Err(_) => 0,
+        };
+        let column_width = match env::var("COMPARE_OLAP_WIDTH") {
+            Ok(v) if v == "full" || v == "0" => None,
+            Ok(v) => match v.parse() {
+                Ok(n) => Some(n),
+                _ => bail!("COMPARE_OLAP_WIDTH must be a positive integer or full, got {v}"),
+            },
+            Err(_) => Some(DEFAULT_COLUMN_WIDTH),
+        };
+        let plan_output = match env::var_os("PLAN_OUTPUT") {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                fs::create_dir_all(&path)
+                    .with_context(|| format!("Failed to create PLAN_OUTPUT={}", path.display()))?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            format: OutputFormat::from_env()?,
+            recorder: BenchRecorder::from_env()?,
+            summary: Default::default(),
+            iterations,
+            warmup,
+            polars_streaming: false,
+            measure_memory: false,
+            column_width,
+            timeout: None,
+            max_print_rows: Some(DEFAULT_MAX_PRINT_ROWS),
+            duck_explain: false,
+            plan_output,
+            quiet: matches!(env::var("COMPARE_OLAP_QUIET"), Ok(v) if !v.is_empty() && v != "0"),
+        })
+    }
+}
+
+/// Coefficient of variation above which timings are considered unstable.
+const UNSTABLE_CV: f64 = 0.10;
+
+/// Durations of repeated runs of the same query.
+pub struct Timings {
+    /// In the order the runs were executed.
+    durations: Vec<Duration>,
+    sorted: Vec<Duration>,
+    /// Set when `ExecOptions::measure_memory` is enabled.
+    pub peak_memory: Option<PeakMemory>,
+}
+
+impl Timings {
+    fn new() -> Self {
+        Self {
+            durations: vec![],
+            sorted: vec![],
+            peak_memory: None,
+        }
+    }
+
+    fn push(&mut self, d: Duration) {
+        self.durations.push(d);
+    }
+
+    fn finish(mut self) -> Self {
+        self.sorted = self.durations.clone();
+        self.sorted.sort();
+        self
+    }
+
+    /// Durations in the order the runs were executed.
+    pub fn durations(&self) -> &[Duration] {
+        &self.durations
+    }
+
+    pub fn median(&self) -> Duration {
+        self.sorted[self.sorted.len() / 2]
+    }
+
+    pub fn min(&self) -> Duration {
+        self.sorted[0]
+    }
+
+    pub fn max(&self) -> Duration {
+        self.sorted[self.sorted.len() - 1]
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.durations.iter().sum::<Duration>() / self.durations.len() as u32
+    }
+
+    /// Sample standard deviation, zero for a single run.
+    pub fn std_dev(&self) -> Duration {
+        let n = self.durations.len();
+        if n < 2 {
+            return Duration::ZERO;
+        }
+        let mean = self.mean().as_secs_f64();
+        let sum_sq: f64 = self
+            .durations
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean).powi(2))
+            .sum();
+        Duration::from_secs_f64((sum_sq / (n - 1) as f64).sqrt())
+    }
+
+    /// Coefficient of variation, standard deviation relative to the mean.
+    pub fn cv(&self) -> f64 {
+        let mean = self.mean().as_secs_f64();
+        if mean == 0.0 {
+            return 0.0;
+        }
+        self.std_dev().as_secs_f64() / mean
+    }
+
+    fn print(&self, label: &str, opts: &ExecOptions) {
+        let colored = opts.format == OutputFormat::Table && std::io::stdout().is_terminal();
+        let label = if colored {
+            format!("\x1b[1;32m{label}\x1b[0m")
+        } else {
+            label.to_string()
+        };
+        if self.durations.len() == 1 {
+            out!("{} took {}ms", label, self.median().as_millis());
+        } else {
+            out!(
+                "{} took {}ms (median of {}, min {}ms, max {}ms",
+                label,
+                self.median().as_millis(),
+                self.durations.len(),
+                self.min().as_millis(),
+                self.max().as_millis(),
+            );
+            out!(
+                ", mean {:.1}ms ± {:.1}ms, CV {:.1}%)",
+                self.mean().as_secs_f64() * 1000.0,
+                self.std_dev().as_secs_f64() * 1000.0,
+                self.cv() * 100.0,
+            );
+        }
+        match self.peak_memory {
+            Some(PeakMemory::Bytes(bytes)) => out!(", peak RSS {}MiB", bytes / 1024 / 1024),
+            Some(PeakMemory::Unsupported) => out!(", peak RSS unsupported"),
+            None => {}
+        }
+        outln!();
+        if self.durations.len() > 1 && self.cv() > UNSTABLE_CV {
+            outln!(
+                "Warning: timings vary by {:.0}% (CV), consider more iterations with BENCH_ITERS",
+                self.cv() * 100.0
+            );
+        }
+        outln!();
+    }
+}
+
+/// Peak resident set size of the whole process while a query ran.
+#[derive(Debug, Clone, Copy)]
+pub enum PeakMemory {
+    Bytes(u64),
+    /// Only Linux allows resetting the peak, see `/proc/self/clear_refs`.
+    Unsupported,
+}
+
+impl PeakMemory {
+    /// Reset the peak RSS counter to the current RSS. Returns false if that's not supported.
+    fn reset() -> bool {
+        cfg!(target_os = "linux") && fs::write("/proc/self/clear_refs", "5").is_ok()
+    }
+
+    /// Read `VmHWM` from `/proc/self/status`.
+    fn read() -> Self {
+        let Ok(status) = fs::read_to_string("/proc/self/status") else {
+            return Self::Unsupported;
+        };
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmHWM:"))
+            .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kb| Self::Bytes(kb * 1024))
+            .unwrap_or(Self::Unsupported)
+    }
+
+    /// Start measuring when enabled. Pass the result to [`PeakMemory::finish`].
+    fn start(opts: &ExecOptions) -> Option<bool> {
+        opts.measure_memory.then(Self::reset)
+    }
+
+    fn finish(started: Option<bool>) -> Option<Self> {
+        started.map(|reset| if reset { Self::read() } else { Self::Unsupported })
+    }
+}
+
+/// Run the query `opts.warmup` times untimed and then `opts.iterations` times.
+/// Returns the last result.
+fn measure<T>(opts: &ExecOptions, mut run: impl FnMut() -> Result<T>) -> Result<(T, Timings)> {
+    // Warmup runs are not timed but they still must succeed, otherwise the error is returned.
+    for _ in 0..opts.warmup {
+        run()?;
+    }
+
+    let mut timings = Timings::new();
+    let mut last = None;
+    let memory = PeakMemory::start(opts);
+    for _ in 0..opts.iterations.max(1) {
+        let now = Instant::now();
+        last = Some(run()?);
+        timings.push(now.elapsed());
+    }
+    timings.peak_memory = PeakMemory::finish(memory);
+    Ok((last.unwrap(), timings.finish()))
+}
+
+/// Time to open an engine and run its first query next to the median of the following runs.
+pub struct ColdStart {
+    pub engine: Engine,
+    pub cold: Duration,
+    pub warm: Duration,
+}
+
+/// Time `open` together with the first `query`, then the warm runs of `query` on the same
+/// connection as configured in `opts`.
+pub fn cold_start<C, T>(
+    engine: Engine,
+    opts: &ExecOptions,
+    open: impl FnOnce() -> Result<C>,
+    mut query: impl FnMut(&C) -> Result<T>,
+) -> Result<ColdStart> {
     let now = Instant::now();
+    let conn = open()?;
+    query(&conn)?;
+    let cold = now.elapsed();
+    let (_, timings) = measure(opts, || query(&conn))?;
+    Ok(ColdStart {
+        engine,
+        cold,
+        warm: timings.median(),
+    })
+}
+
+/// Print cold and warm timings per engine, fastest cold start first.
+pub fn print_cold_start(rows: &mut [ColdStart]) {
+    rows.sort_by_key(|row| row.cold);
+    outln!("{:<20} {:>10} {:>10}", "Engine", "cold ms", "warm ms");
+    for row in rows.iter() {
+        outln!(
+            "{:<20} {:>10.1} {:>10.1}",
+            row.engine.label(),
+            row.cold.as_secs_f64() * 1000.0,
+            row.warm.as_secs_f64() * 1000.0,
+        );
+    }
+}
+
+/// Print the on-disk size of each file. SQLite databases in WAL mode include their `-wal` file.
+/// Files that don't exist are skipped.
+pub fn report_sizes(paths: &[String]) {
+    let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    outln!("File sizes:");
+    for path in paths {
+        let Ok(metadata) = fs::metadata(path) else {
+            continue;
+        };
+        match fs::metadata(format!("{path}-wal")) {
+            Ok(wal) => outln!(
+                "  {path}: {:.1} MiB (with {:.1} MiB WAL)",
+                mib(metadata.len() + wal.len()),
+                mib(wal.len())
+            ),
+            Err(_) => outln!("  {path}: {:.1} MiB", mib(metadata.len())),
+        }
+    }
+}
+
+/// Versions of the bundled SQLite and DuckDB libraries and of the Polars and DataFusion crates.
+pub fn engine_versions() -> Result<Vec<(&'static str, String)>> {
+    let sqlite = rusqlite::Connection::open_in_memory()?;
+    let sqlite_version = sqlite.query_row("SELECT sqlite_version()", [], |row| row.get(0))?;
+    let duck = duckdb::Connection::open_in_memory()?;
+    let duck_version = duck.query_row("SELECT version()", [], |row| row.get(0))?;
+
+    Ok(vec![
+        ("SQLite", sqlite_version),
+        ("DuckDB", duck_version),
+        ("Polars", polars::VERSION.to_string()),
+        ("DataFusion", datafusion::DATAFUSION_VERSION.to_string()),
+    ])
+}
+
+/// Print engine versions and add them to the `BENCH_OUTPUT` file.
+pub fn print_versions(opts: &ExecOptions) -> Result<()> {
+    let versions = engine_versions()?;
+    let line: Vec<_> = versions.iter().map(|(engine, v)| format!("{engine} {v}")).collect();
+    outln!("Versions: {}", line.join(", "));
+    opts.recorder.record_versions(&versions)
+}
+
+/// Returned by `exec_*` functions when a query ran longer than [`ExecOptions::timeout`].
+#[derive(Debug)]
+pub struct QueryTimedOut(pub Duration);
+
+impl fmt::Display for QueryTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for QueryTimedOut {}
+
+/// Run `query` and call `interrupt` from a watchdog thread once it exceeds `timeout`.
+///
+/// The query finishing and the watchdog interrupting it race for the same state, so a query
+/// that finishes right at the timeout is either interrupted and reported as timed out, or
+/// returns its result and is never interrupted.
+fn interrupt_after<T>(
+    timeout: Option<Duration>,
+    interrupt: impl FnOnce() + Send,
+    query: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    const RUNNING: u8 = 0;
+    const FINISHED: u8 = 1;
+    const INTERRUPTED: u8 = 2;
+
+    let Some(timeout) = timeout else {
+        return query();
+    };
+
+    let state = AtomicU8::new(RUNNING);
+    let claim = |to: u8| {
+        state
+            .compare_exchange(RUNNING, to, atomic::Ordering::SeqCst, atomic::Ordering::SeqCst)
+            .is_ok()
+    };
+    let (done, finished) = mpsc::channel::<()>();
+    // The scope joins the watchdog, so an interrupt that won the race lands before the
+    // connection runs anything else.
+    let result = thread::scope(|s| {
+        let claim = &claim;
+        s.spawn(move || {
+            // Dropping `done` disconnects the channel as soon as the query returns.
+            if finished.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout)
+                && claim(INTERRUPTED)
+            {
+                interrupt();
+            }
+        });
+        let result = query();
+        let interrupted = !claim(FINISHED);
+        drop(done);
+        (result, interrupted)
+    });
+
+    match result {
+        (_, true) => Err(QueryTimedOut(timeout).into()),
+        (result, false) => result,
+    }
+}
+
+/// Await `query` for at most `timeout`. Dropping the future cancels the query.
+async fn cancel_after<T>(
+    timeout: Option<Duration>,
+    query: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, query)
+            .await
+            .map_err(|_| QueryTimedOut(timeout))?,
+        None => query.await,
+    }
+}
+
+/// `EXPLAIN QUERY PLAN` rendered as an indented tree like the `sqlite3` shell does.
+pub fn explain_sqlite(conn: &rusqlite::Connection, query: &str) -> Result<String> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {query}"))?;
+    let mut rows = stmt.query([])?;
+    // Rows are (id, parent, notused, detail), parents always come before their children.
+    let mut depths = HashMap::new();
+    let mut plan = String::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let parent: i64 = row.get(1)?;
+        let detail: String = row.get(3)?;
+        let depth = depths.get(&parent).map_or(0, |d| d + 1);
+        depths.insert(id, depth);
+        plan.push_str(&"  ".repeat(depth));
+        plan.push_str(&detail);
+        plan.push('\n');
+    }
+    Ok(plan)
+}
+
+pub fn query_sqlite(conn: &rusqlite::Connection, query: &str) -> Result<QueryResult> {
     let mut stmt = conn.prepare(query)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+    let mut rows = stmt.query([])?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for i in 0..columns.len() {
+            let v: rusqlite::types::Value = row.get(i)?;
+            values.push(v.into());
+        }
+        result.push(values);
+    }
+
+    Ok(QueryResult {
+        columns,
+        rows: result,
+    })
+}
+
+/// Size of the sample kept by the SQLite `approx_quantile` aggregate.
+pub const APPROX_QUANTILE_SAMPLE: usize = 10_000;
+
+/// Register custom SQL functions on a SQLite connection:
+///
+/// * `approx_quantile(value, q)` aggregate estimating the `q` quantile from a uniform reservoir
+///   sample of [`APPROX_QUANTILE_SAMPLE`] values. Exact for smaller inputs, otherwise the rank
+///   of the result is within ±1.4% of `q` with 95% confidence (DKW inequality).
+pub fn register_sqlite_functions(conn: &rusqlite::Connection) -> Result<()> {
+    use rusqlite::functions::FunctionFlags;
+
+    conn.create_aggregate_function(
+        "approx_quantile",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        ApproxQuantile,
+    )?;
+    #[cfg(feature = "msgpack")]
+    conn.create_scalar_function(
+        "msgpack_get",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        msgpack_get,
+    )?;
+    Ok(())
+}
+
+/// SQLite function `msgpack_get(payload, '$.fields[0].value')`, the `->>` operator for
+/// MessagePack BLOBs. Numbers are returned as numbers, objects and arrays as JSON text.
+#[cfg(feature = "msgpack")]
+fn msgpack_get(
+    ctx: &rusqlite::functions::Context<'_>,
+) -> rusqlite::Result<rusqlite::types::Value> {
+    use rusqlite::{types::Value, Error::UserFunctionError};
+
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    // The path is a literal, parse it once per statement.
+    let path = ctx.get_or_create_aux(1, |path| -> Result<_, BoxError> {
+        Ok(parse_json_path(path.as_str()?)?)
+    })?;
+    let Some(bytes) = ctx.get_raw(0).as_blob_or_null()? else {
+        return Ok(Value::Null);
+    };
+    let payload: serde_json::Value =
+        rmp_serde::from_slice(bytes).map_err(|err| UserFunctionError(err.into()))?;
+    Ok(match json_path_value(&payload, &path) {
+        None | Some(serde_json::Value::Null) => Value::Null,
+        Some(serde_json::Value::Bool(b)) => Value::Integer(*b as i64),
+        Some(serde_json::Value::Number(n)) => match n.as_i64() {
+            Some(n) => Value::Integer(n),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        Some(serde_json::Value::String(s)) => Value::Text(s.clone()),
+        Some(other) => Value::Text(other.to_string()),
+    })
+}
+
+struct ApproxQuantile;
+
+struct Reservoir {
+    values: Vec<f64>,
+    seen: u64,
+    q: f64,
+    rng: rand::rngs::StdRng,
+}
+
+impl rusqlite::functions::Aggregate<Reservoir, Option<f64>> for ApproxQuantile {
+    fn init(&self, _: &mut rusqlite::functions::Context<'_>) -> rusqlite::Result<Reservoir> {
+        use rand::SeedableRng;
+
+        Ok(Reservoir {
+            values: Vec::new(),
+            seen: 0,
+            q: 0.0,
+            // Fixed seed so that the same data always gives the same estimate.
+            rng: rand::rngs::StdRng::seed_from_u64(0),
+        })
+    }
+
+    fn step(
+        &self,
+        ctx: &mut rusqlite::functions::Context<'_>,
+        acc: &mut Reservoir,
+    ) -> rusqlite::Result<()> {
+        use rand::Rng;
+
+        let Some(value) = ctx.get::<Option<f64>>(0)? else {
+            return Ok(());
+        };
+        let q: f64 = ctx.get(1)?;
+        if !(0.0..=1.0).contains(&q) {
+            return Err(rusqlite::Error::UserFunctionError(
+                format!("approx_quantile q must be between 0 and 1, got {q}").into(),
+            ));
+        }
+        acc.q = q;
+
+        acc.seen += 1;
+        if acc.values.len() < APPROX_QUANTILE_SAMPLE {
+            acc.values.push(value);
+        } else {
+            let i = acc.rng.gen_range(0..acc.seen);
+            if (i as usize) < APPROX_QUANTILE_SAMPLE {
+                acc.values[i as usize] = value;
+            }
+        }
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        _: &mut rusqlite::functions::Context<'_>,
+        acc: Option<Reservoir>,
+    ) -> rusqlite::Result<Option<f64>> {
+        let Some(mut acc) = acc.filter(|acc| !acc.values.is_empty()) else {
+            return Ok(None);
+        };
+        acc.values.sort_by(|a, b| a.total_cmp(b));
+
+        // Interpolate between the closest ranks like `quantile_cont`.
+        let pos = (acc.values.len() - 1) as f64 * acc.q;
+        let lower = pos.floor() as usize;
+        let upper = pos.ceil() as usize;
+        let fraction = pos - lower as f64;
+        Ok(Some(acc.values[lower] * (1.0 - fraction) + acc.values[upper] * fraction))
+    }
+}
 
-    let column_len = {
-        let columns = stmt.column_names();
-        print_column_names(&columns);
-        columns.len()
-    };
+pub fn exec_sqlite(
+    conn: &rusqlite::Connection,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    do_exec_sqlite("SQLite", conn, name, query, opts)
+}
+
+/// Same as [`exec_sqlite`] for the normalized schema, timed under its own label.
+pub fn exec_sqlite_normalized(
+    conn: &rusqlite::Connection,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    do_exec_sqlite("SQLite (normalized)", conn, name, query, opts)
+}
+
+/// Same as [`exec_sqlite`] for the MessagePack payload, timed under its own label.
+#[cfg(feature = "msgpack")]
+pub fn exec_sqlite_msgpack(
+    conn: &rusqlite::Connection,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    do_exec_sqlite(Engine::SqliteMsgpack.label(), conn, name, query, opts)
+}
+
+fn do_exec_sqlite(
+    label: &str,
+    conn: &rusqlite::Connection,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    let interrupt = conn.get_interrupt_handle();
+    let (result, timings) = measure(opts, || {
+        interrupt_after(opts.timeout, || interrupt.interrupt(), || query_sqlite(conn, query))
+    })?;
+    print_result(&result, opts);
+    timings.print(label, opts);
+    opts.recorder.record(label, name, &timings, result.rows.len())?;
+    opts.summary.add(label, timings.median());
+    Ok(result)
+}
 
+pub fn query_duck(
+    conn: &duckdb::Connection,
+    query: &str,
+    columns: Option<Vec<&str>>,
+) -> Result<QueryResult> {
+    let mut stmt = conn.prepare(query)?;
     let mut rows = stmt.query([])?;
+    // Result schema is only known after the statement is executed,
+    // calling `column_names` on a prepared statement panics.
+    let columns: Vec<String> = match columns {
+        Some(columns) => columns.into_iter().map(String::from).collect(),
+        None => match rows.as_ref() {
+            Some(stmt) => stmt.column_names().into_iter().map(String::from).collect(),
+            None => vec![],
+        },
+    };
+
+    let mut result = Vec::new();
+    // Hardcoded columns can get out of sync with an edited query. Missing cells are printed
+    // empty instead of failing the whole query.
+    let mut warned = false;
     while let Some(row) = rows.next()? {
-        for i in 0..column_len {
-            let v: rusqlite::types::Value = row.get(i)?;
-            print!("| {:<20} ", fmt_sql_value(v));
+        let mut values = Vec::with_capacity(columns.len());
+        for i in 0..columns.len() {
+            match row.get::<_, duckdb::types::Value>(i) {
+                Ok(v) => values.push(v.into()),
+                Err(err) => {
+                    if !warned {
+                        tracing::warn!("Failed to read DuckDB column {i} ({}): {err}", columns[i]);
+                        warned = true;
+                    }
+                    values.push(CellValue::Unreadable);
+                }
+            }
         }
-        println!("|");
+        result.push(values);
     }
 
-    print_divider(column_len);
-    println!("SQLite took {}ms", now.elapsed().as_millis());
-    println!();
-    Ok(())
+    Ok(QueryResult {
+        columns,
+        rows: result,
+    })
+}
+
+pub fn exec_duck(
+    conn: &duckdb::Connection,
+    name: &str,
+    query: &str,
+    columns: Option<Vec<&str>>,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    do_exec_duck("DuckDB", conn, name, query, columns, opts)
+}
+
+pub fn exec_duck_typed(
+    conn: &duckdb::Connection,
+    name: &str,
+    query: &str,
+    columns: Option<Vec<&str>>,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    do_exec_duck("DuckDB (Typed)", conn, name, query, columns, opts)
+}
+
+/// Run a typed DuckDB query against a connection from [`open_duck_parquet`].
+pub fn exec_duck_parquet(
+    conn: &duckdb::Connection,
+    name: &str,
+    query: &str,
+    columns: Option<Vec<&str>>,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    do_exec_duck(Engine::DuckParquet.label(), conn, name, query, columns, opts)
+}
+
+/// In-memory DuckDB connection with an `events` view over the parquet file. The file has the
+/// schema of the typed DuckDB table, so typed queries run unchanged against the parquet scan.
+pub fn open_duck_parquet(path: &str) -> Result<duckdb::Connection> {
+    let conn = duckdb::Connection::open_in_memory()?;
+    let path = path.replace('\'', "''");
+    conn.execute_batch(&format!("CREATE VIEW events AS SELECT * FROM read_parquet('{path}')"))?;
+    Ok(conn)
 }
 
-pub fn exec_duck(conn: &duckdb::Connection, query: &str, columns: Vec<&str>) -> Result<()> {
-    do_exec_duck("DuckDB", conn, query, columns)
+/// How many times [`ConnectionFactory`] retries opening a database by default.
+const DEFAULT_OPEN_RETRIES: u32 = 5;
+
+/// Opens a fresh read-only connection to a SQLite or DuckDB file for each worker thread.
+///
+/// Connections can't be shared across threads, but read-only connections to the same file
+/// don't contend for locks so engines can run queries in parallel. Opening is retried with
+/// exponential backoff while a writer (e.g. `gen_data --append`) still holds the file.
+#[derive(Clone)]
+pub struct ConnectionFactory {
+    path: String,
+    retries: u32,
 }
 
-pub fn exec_duck_typed(conn: &duckdb::Connection, query: &str, columns: Vec<&str>) -> Result<()> {
-    do_exec_duck("DuckDB (Typed)", conn, query, columns)
+impl ConnectionFactory {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            retries: DEFAULT_OPEN_RETRIES,
+        }
+    }
+
+    /// How many times a failed open is retried, 0 fails on the first error.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Read-only SQLite connection with [`register_sqlite_functions`] registered.
+    pub fn sqlite(&self) -> Result<rusqlite::Connection> {
+        use rusqlite::OpenFlags;
+
+        let conn = self.retry(|| {
+            let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+            Ok(rusqlite::Connection::open_with_flags(&self.path, flags)?)
+        })?;
+        register_sqlite_functions(&conn)?;
+        Ok(conn)
+    }
+
+    /// Read-only DuckDB connection.
+    pub fn duck(&self) -> Result<duckdb::Connection> {
+        self.retry(|| {
+            let config = duckdb::Config::default().access_mode(duckdb::AccessMode::ReadOnly)?;
+            Ok(duckdb::Connection::open_with_flags(&self.path, config)?)
+        })
+    }
+
+    fn retry<T>(&self, mut open: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut delay = Duration::from_millis(10);
+        let mut attempt = 0;
+        loop {
+            match open() {
+                Err(err) if attempt < self.retries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "Failed to open {}, retry {attempt}/{} in {delay:?}: {err:#}",
+                        self.path,
+                        self.retries
+                    );
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                res => return res.with_context(|| format!("Failed to open {}", self.path)),
+            }
+        }
+    }
 }
 
 fn do_exec_duck(
     label: &str,
     conn: &duckdb::Connection,
+    name: &str,
     query: &str,
-    columns: Vec<&str>,
-) -> Result<()> {
-    let now = Instant::now();
-    let mut stmt = conn.prepare(query)?;
+    columns: Option<Vec<&str>>,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    let interrupt = conn.interrupt_handle();
+    let (result, timings) = measure(opts, || {
+        interrupt_after(
+            opts.timeout,
+            || interrupt.interrupt(),
+            || query_duck(conn, query, columns.clone()),
+        )
+    })?;
+    print_result(&result, opts);
+    timings.print(label, opts);
+    opts.recorder.record(label, name, &timings, result.rows.len())?;
+    opts.summary.add(label, timings.median());
+    if opts.duck_explain {
+        outln!("{label} plan:");
+        outln!("{}", explain_analyze_duck(conn, query)?);
+    }
+    Ok(result)
+}
+
+/// Run the query under `EXPLAIN ANALYZE` and return the plan with per-operator timings.
+pub fn explain_analyze_duck(conn: &duckdb::Connection, query: &str) -> Result<String> {
+    duck_plan(conn, &format!("EXPLAIN ANALYZE {query}"))
+}
+
+/// Physical plan from DuckDB's `EXPLAIN` without running the query.
+pub fn explain_duck(conn: &duckdb::Connection, query: &str) -> Result<String> {
+    duck_plan(conn, &format!("EXPLAIN {query}"))
+}
+
+fn duck_plan(conn: &duckdb::Connection, explain: &str) -> Result<String> {
+    let mut stmt = conn.prepare(explain)?;
+    let mut rows = stmt.query([])?;
+    // Rows are (explain_key, explain_value) pairs, the value holds the rendered plan.
+    let mut plan = String::new();
+    while let Some(row) = rows.next()? {
+        let value: String = row.get(1)?;
+        plan.push_str(&value);
+    }
+    Ok(plan)
+}
+
+/// Open (or create) an embedded ClickHouse instance storing data in `path`.
+#[cfg(feature = "clickhouse")]
+pub fn open_clickhouse(path: &str) -> Result<chdb_rust::session::Session> {
+    let session = chdb_rust::session::SessionBuilder::new()
+        .with_data_path(path)
+        .build()?;
+    Ok(session)
+}
+
+#[cfg(feature = "clickhouse")]
+pub fn query_clickhouse(
+    session: &chdb_rust::session::Session,
+    query: &str,
+) -> Result<QueryResult> {
+    use chdb_rust::{arg::Arg, format::OutputFormat};
+
+    // 64-bit integers are quoted in JSON by default.
+    let query = format!("{query} SETTINGS output_format_json_quote_64bit_integers = 0");
+    let result = session.execute(
+        &query,
+        Some(&[Arg::OutputFormat(OutputFormat::JSONCompactEachRowWithNames)]),
+    )?;
+
+    let data = result.data_utf8_lossy();
+    let mut lines = data.lines();
+    let columns: Vec<String> = match lines.next() {
+        Some(header) => serde_json::from_str(header)?,
+        None => vec![],
+    };
+
+    let mut rows = Vec::new();
+    for line in lines {
+        let values: Vec<serde_json::Value> = serde_json::from_str(line)?;
+        rows.push(values.into_iter().map(CellValue::from).collect());
+    }
+
+    Ok(QueryResult { columns, rows })
+}
+
+#[cfg(feature = "clickhouse")]
+pub fn exec_clickhouse(
+    session: &chdb_rust::session::Session,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    let (result, timings) = measure(opts, || query_clickhouse(session, query))?;
+    print_result(&result, opts);
+    timings.print("ClickHouse", opts);
+    opts.recorder.record("ClickHouse", name, &timings, result.rows.len())?;
+    opts.summary.add("ClickHouse", timings.median());
+    Ok(result)
+}
+
+/// Connect to the Postgres server from `DATABASE_URL`. Returns `None` when the variable is unset.
+#[cfg(feature = "postgres")]
+pub async fn connect_postgres() -> Result<Option<tokio_postgres::Client>> {
+    let Ok(url) = env::var("DATABASE_URL") else {
+        tracing::warn!("DATABASE_URL is not set, skipping Postgres");
+        return Ok(None);
+    };
+    let (client, connection) = tokio_postgres::connect(&url, tokio_postgres::NoTls)
+        .await
+        .context("Failed to connect to Postgres")?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            tracing::error!("Postgres connection error: {err}");
+        }
+    });
+    Ok(Some(client))
+}
+
+/// Run the query with the simple query protocol. Every value comes back as text, numbers are
+/// parsed back so that results can be compared with other engines.
+#[cfg(feature = "postgres")]
+pub async fn query_postgres(client: &tokio_postgres::Client, query: &str) -> Result<QueryResult> {
+    use tokio_postgres::SimpleQueryMessage;
+
+    let mut columns = vec![];
+    let mut rows = vec![];
+    for message in client.simple_query(query).await? {
+        let SimpleQueryMessage::Row(row) = message else {
+            continue;
+        };
+        if columns.is_empty() {
+            columns = row.columns().iter().map(|c| c.name().to_string()).collect();
+        }
+        let values = (0..row.len())
+            .map(|i| match row.get(i) {
+                None => CellValue::Null,
+                Some(v) => {
+                    if let Ok(n) = v.parse::<i64>() {
+                        CellValue::Int(n)
+                    } else if let Ok(f) = v.parse::<f64>() {
+                        CellValue::Float(f)
+                    } else {
+                        CellValue::Text(v.to_string())
+                    }
+                }
+            })
+            .collect();
+        rows.push(values);
+    }
+
+    Ok(QueryResult { columns, rows })
+}
+
+#[cfg(feature = "postgres")]
+pub async fn exec_postgres(
+    client: &tokio_postgres::Client,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    for _ in 0..opts.warmup {
+        query_postgres_within(client, query, opts.timeout).await?;
+    }
 
-    let column_len = {
-        // This panics
-        // let columns = stmt.column_names();
-        print_column_names(&columns);
-        columns.len()
+    let mut timings = Timings::new();
+    let mut result = QueryResult {
+        columns: vec![],
+        rows: vec![],
     };
+    let memory = PeakMemory::start(opts);
+    for _ in 0..opts.iterations.max(1) {
+        let now = Instant::now();
+        result = query_postgres_within(client, query, opts.timeout).await?;
+        timings.push(now.elapsed());
+    }
+    timings.peak_memory = PeakMemory::finish(memory);
+    let timings = timings.finish();
+
+    print_result(&result, opts);
+    timings.print("Postgres", opts);
+    opts.recorder.record("Postgres", name, &timings, result.rows.len())?;
+    opts.summary.add("Postgres", timings.median());
+    Ok(result)
+}
+
+#[cfg(feature = "postgres")]
+async fn query_postgres_within(
+    client: &tokio_postgres::Client,
+    query: &str,
+    timeout: Option<Duration>,
+) -> Result<QueryResult> {
+    let result = cancel_after(timeout, query_postgres(client, query)).await;
+    if matches!(&result, Err(err) if err.is::<QueryTimedOut>()) {
+        // The server would keep running it and the following queries would queue behind.
+        client.cancel_token().cancel_query(tokio_postgres::NoTls).await?;
+    }
+    result
+}
+
+/// Load a SQLite table into memory and register it in DataFusion under the same name.
+/// Columns are typed from the declared SQLite types, `payload` JSON stays a Utf8 column.
+pub fn register_sqlite(ctx: &SessionContext, path: &str, table: &str) -> Result<()> {
+    let conn = rusqlite::Connection::open(path)?;
+
+    let mut stmt = conn.prepare("SELECT name, type FROM pragma_table_info(?1)")?;
+    let fields: Vec<Field> = stmt
+        .query_map([table], |row| {
+            let name: String = row.get(0)?;
+            let decl_type: String = row.get(1)?;
+            let data_type = match (name.as_str(), decl_type.to_uppercase().as_str()) {
+                // SQLite has no timestamp type, chrono values are stored as text.
+                ("timestamp", _) => DataType::Timestamp(TimeUnit::Microsecond, None),
+                (_, "INTEGER") => DataType::Int64,
+                (_, "REAL") => DataType::Float64,
+                _ => DataType::Utf8,
+            };
+            Ok(Field::new(name, data_type, true))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    if fields.is_empty() {
+        bail!("SQLite table {table} not found in {path}");
+    }
 
+    let mut columns = vec![Vec::new(); fields.len()];
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {table}"))?;
     let mut rows = stmt.query([])?;
     while let Some(row) = rows.next()? {
-        for i in 0..column_len {
-            let v: duckdb::types::Value = row.get(i)?;
-            print!("| {:<20} ", fmt_duck_value(v));
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.push(row.get::<_, rusqlite::types::Value>(i)?);
         }
-        println!("|");
     }
 
-    print_divider(column_len);
-    println!("{} took {}ms", label, now.elapsed().as_millis());
-    println!();
+    let arrays = fields
+        .iter()
+        .zip(columns)
+        .map(|(field, values)| sqlite_array(field.data_type(), values))
+        .collect::<Result<Vec<_>>>()?;
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?;
+    ctx.register_batch(table, batch)?;
     Ok(())
 }
 
-pub async fn exec_df(ctx: &SessionContext, query: &str) -> Result<()> {
-    let now = Instant::now();
-    let df = ctx.sql(query).await?;
-    df.show().await?;
-    println!("DataFusions took {}ms", now.elapsed().as_millis());
-    println!();
-    Ok(())
+fn sqlite_array(data_type: &DataType, values: Vec<rusqlite::types::Value>) -> Result<ArrayRef> {
+    use rusqlite::types::Value;
+
+    let array: ArrayRef = match data_type {
+        DataType::Int64 => Arc::new(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Value::Integer(n) => Some(n),
+                    _ => None,
+                })
+                .collect::<Int64Array>(),
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Value::Integer(n) => Some(n as f64),
+                    Value::Real(f) => Some(f),
+                    _ => None,
+                })
+                .collect::<Float64Array>(),
+        ),
+        DataType::Timestamp(_, _) => Arc::new(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Value::Text(t) => {
+                        let ts = chrono::DateTime::parse_from_str(&t, "%F %T%.f%:z")
+                            .with_context(|| format!("Invalid timestamp {t}"))?;
+                        Ok(Some(ts.timestamp_micros()))
+                    }
+                    _ => Ok(None),
+                })
+                .collect::<Result<TimestampMicrosecondArray>>()?,
+        ),
+        _ => Arc::new(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Value::Null => None,
+                    Value::Text(t) => Some(t),
+                    Value::Integer(n) => Some(n.to_string()),
+                    Value::Real(f) => Some(f.to_string()),
+                    Value::Blob(b) => Some(String::from_utf8_lossy(&b).into_owned()),
+                })
+                .collect::<StringArray>(),
+        ),
+    };
+    Ok(array)
+}
+
+/// Scalar UDF `json_get(value, '$.fields[0].value')` returning the value at the path as a string.
+/// Works on JSON strings as well as on nested struct and list columns.
+pub fn json_get_udf() -> ScalarUDF {
+    let return_type: ReturnTypeFunction = Arc::new(|_| Ok(Arc::new(DataType::Utf8)));
+    let fun: ScalarFunctionImplementation = Arc::new(|args: &[ColumnarValue]| {
+        let path = match &args[1] {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(path))) => parse_json_path(path)?,
+            _ => {
+                return Err(DataFusionError::Execution(
+                    "json_get path must be a string literal".to_string(),
+                ))
+            }
+        };
+        let array = match &args[0] {
+            ColumnarValue::Array(array) => array.clone(),
+            ColumnarValue::Scalar(scalar) => scalar.to_array(),
+        };
+        let values = (0..array.len())
+            .map(|row| json_get(&array, row, &path))
+            .collect::<datafusion::error::Result<StringArray>>()?;
+        Ok(ColumnarValue::Array(Arc::new(values)))
+    });
+
+    ScalarUDF::new(
+        "json_get",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse SQLite style JSON path, e.g. `$.fields[0].value`.
+fn parse_json_path(path: &str) -> datafusion::error::Result<Vec<JsonPathSegment>> {
+    let mut segments = vec![];
+    for part in path.trim_start_matches('$').split('.').filter(|p| !p.is_empty()) {
+        let mut pieces = part.split('[');
+        if let Some(key) = pieces.next().filter(|k| !k.is_empty()) {
+            segments.push(JsonPathSegment::Key(key.to_string()));
+        }
+        for index in pieces {
+            let index = index.trim_end_matches(']').parse().map_err(|_| {
+                DataFusionError::Execution(format!("Invalid json_get path {path}"))
+            })?;
+            segments.push(JsonPathSegment::Index(index));
+        }
+    }
+    Ok(segments)
+}
+
+/// Value at `path` in a parsed JSON document.
+fn json_path_value<'a>(
+    json: &'a serde_json::Value,
+    path: &[JsonPathSegment],
+) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(json, |value, segment| match segment {
+        JsonPathSegment::Key(key) => value.get(key.as_str()),
+        JsonPathSegment::Index(i) => value.get(*i),
+    })
+}
+
+fn json_get(
+    array: &ArrayRef,
+    row: usize,
+    path: &[JsonPathSegment],
+) -> datafusion::error::Result<Option<String>> {
+    if array.is_null(row) {
+        return Ok(None);
+    }
+
+    match (array.data_type(), path.first()) {
+        (DataType::Utf8, Some(_)) => {
+            let text = as_string_array(array).value(row);
+            let json: serde_json::Value = serde_json::from_str(text)
+                .map_err(|err| DataFusionError::Execution(format!("json_get: {err}")))?;
+            Ok(match json_path_value(&json, path) {
+                None | Some(serde_json::Value::Null) => None,
+                Some(serde_json::Value::String(s)) => Some(s.clone()),
+                Some(other) => Some(other.to_string()),
+            })
+        }
+        (_, None) => Ok(Some(array_value_to_string(array, row)?)),
+        (DataType::Struct(_), Some(JsonPathSegment::Key(key))) => {
+            match as_struct_array(array).column_by_name(key) {
+                Some(child) => json_get(child, row, &path[1..]),
+                None => Ok(None),
+            }
+        }
+        (DataType::List(_), Some(JsonPathSegment::Index(i))) => {
+            let items = as_list_array(array).value(row);
+            if *i < items.len() {
+                json_get(&items, *i, &path[1..])
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Run a DataFusion query and return its record batches.
+pub async fn collect_df(ctx: &SessionContext, query: &str) -> Result<Vec<RecordBatch>> {
+    Ok(ctx.sql(query).await?.collect().await?)
+}
+
+/// First `n` rows of `batches`.
+fn head_batches(batches: &[RecordBatch], mut n: usize) -> Vec<RecordBatch> {
+    let mut head = vec![];
+    for batch in batches {
+        if n == 0 {
+            break;
+        }
+        let rows = batch.num_rows().min(n);
+        head.push(batch.slice(0, rows));
+        n -= rows;
+    }
+    head
+}
+
+/// Logical and physical plans of a DataFusion query, e.g. to check that filters are pushed
+/// down into the parquet scan.
+pub async fn explain_df(ctx: &SessionContext, query: &str) -> Result<String> {
+    let batches = ctx.sql(query).await?.explain(false, false)?.collect().await?;
+    Ok(pretty_format_batches(&batches)?.to_string())
+}
+
+pub async fn exec_df(
+    ctx: &SessionContext,
+    name: &str,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    // Warmup runs are not timed but they still must succeed, otherwise the error is returned.
+    for _ in 0..opts.warmup {
+        cancel_after(opts.timeout, collect_df(ctx, query)).await?;
+    }
+
+    let mut timings = Timings::new();
+    let mut batches = vec![];
+    let memory = PeakMemory::start(opts);
+    for _ in 0..opts.iterations.max(1) {
+        let now = Instant::now();
+        batches = cancel_after(opts.timeout, collect_df(ctx, query)).await?;
+        timings.push(now.elapsed());
+    }
+    timings.peak_memory = PeakMemory::finish(memory);
+    let timings = timings.finish();
+
+    let result = QueryResult::from_batches(&batches)?;
+    if opts.format == OutputFormat::Plain && !opts.quiet {
+        let shown = opts.max_print_rows.unwrap_or(result.rows.len());
+        outln!("{}", pretty_format_batches(&head_batches(&batches, shown))?);
+        print_hidden_rows(result.rows.len().saturating_sub(shown));
+    } else {
+        print_result(&result, opts);
+    }
+    let label = Engine::DataFusion.label();
+    timings.print(label, opts);
+    opts.recorder.record(label, name, &timings, result.rows.len())?;
+    opts.summary.add(label, timings.median());
+    Ok(result)
+}
+
+pub fn exec_polars(name: &str, lf: LazyFrame, opts: &ExecOptions) -> Result<QueryResult> {
+    let mut streaming = opts.polars_streaming && polars_streams(&lf);
+    if opts.polars_streaming && !streaming {
+        tracing::warn!("Polars query {name} doesn't support streaming, collecting in memory");
+    }
+
+    let collect = |streaming: bool| {
+        measure(opts, || collect_polars(lf.clone().with_streaming(streaming), opts.timeout))
+    };
+    let (df, timings) = match collect(streaming) {
+        Err(err) if streaming && !err.is::<QueryTimedOut>() => {
+            tracing::warn!("Polars streaming failed for {name}, collecting in memory: {err:#}");
+            streaming = false;
+            collect(false)?
+        }
+        res => res?,
+    };
+    let result = QueryResult::from_dataframe(&df)?;
+    if opts.format == OutputFormat::Plain && !opts.quiet {
+        let shown = opts.max_print_rows.unwrap_or(df.height());
+        outln!("{:?}", df.head(Some(shown)));
+        print_hidden_rows(df.height().saturating_sub(shown));
+    } else {
+        print_result(&result, opts);
+    }
+    timings.print(if streaming { "Polars (streaming)" } else { "Polars" }, opts);
+    opts.recorder.record("Polars", name, &timings, result.rows.len())?;
+    opts.summary.add("Polars", timings.median());
+    Ok(result)
+}
+
+/// Run `query` through the Polars SQL interface with `events` registered as the `events` table.
+/// Parsing and planning the SQL is part of the measured time.
+pub fn exec_polars_sql(
+    name: &str,
+    events: LazyFrame,
+    query: &str,
+    opts: &ExecOptions,
+) -> Result<QueryResult> {
+    let label = Engine::PolarsSql.label();
+    let (df, timings) = measure(opts, || {
+        let mut ctx = SQLContext::new();
+        ctx.register("events", events.clone());
+        collect_polars(ctx.execute(query)?, opts.timeout)
+    })?;
+    let result = QueryResult::from_dataframe(&df)?;
+    if opts.format == OutputFormat::Plain && !opts.quiet {
+        let shown = opts.max_print_rows.unwrap_or(df.height());
+        outln!("{:?}", df.head(Some(shown)));
+        print_hidden_rows(df.height().saturating_sub(shown));
+    } else {
+        print_result(&result, opts);
+    }
+    timings.print(label, opts);
+    opts.recorder.record(label, name, &timings, result.rows.len())?;
+    opts.summary.add(label, timings.median());
+    Ok(result)
+}
+
+/// Polars queries can't be interrupted. On timeout the query is abandoned and keeps running
+/// in the background until it completes.
+fn collect_polars(lf: LazyFrame, timeout: Option<Duration>) -> Result<DataFrame> {
+    let Some(timeout) = timeout else {
+        return Ok(lf.collect()?);
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        // The receiver is gone if the query timed out.
+        let _ = tx.send(lf.collect());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(df) => Ok(df?),
+        Err(RecvTimeoutError::Timeout) => Err(QueryTimedOut(timeout).into()),
+        Err(RecvTimeoutError::Disconnected) => bail!("Polars query panicked"),
+    }
+}
+
+/// Optimized plan of a Polars query, shows which predicates and projections reach the scan.
+pub fn explain_polars(lf: LazyFrame, opts: &ExecOptions) -> Result<String> {
+    Ok(lf.with_streaming(opts.polars_streaming).describe_optimized_plan()?)
+}
+
+/// Whether the optimized plan runs (at least partially) on the streaming engine.
+fn polars_streams(lf: &LazyFrame) -> bool {
+    match lf.clone().with_streaming(true).describe_optimized_plan() {
+        Ok(plan) => plan.contains("PIPELINE") || plan.contains("STREAMING"),
+        Err(_) => false,
+    }
+}
+
+impl QueryResult {
+    /// Convert a Polars data frame into rows.
+    pub fn from_dataframe(df: &DataFrame) -> Result<Self> {
+        let columns = df
+            .get_column_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let mut rows = Vec::with_capacity(df.height());
+        for i in 0..df.height() {
+            let row: Vec<CellValue> = df
+                .get_columns()
+                .iter()
+                .map(|s| -> Result<CellValue> { Ok(s.get(i)?.into()) })
+                .collect::<Result<_>>()?;
+            rows.push(row);
+        }
+
+        Ok(Self { columns, rows })
+    }
+
+    /// Convert Arrow record batches (e.g. DataFusion output) into rows.
+    pub fn from_batches(batches: &[RecordBatch]) -> Result<Self> {
+        let columns = match batches.first() {
+            Some(batch) => batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().to_string())
+                .collect(),
+            None => vec![],
+        };
+
+        let mut rows = Vec::new();
+        for batch in batches {
+            let values = batch
+                .columns()
+                .iter()
+                .map(arrow_values)
+                .collect::<Result<Vec<_>>>()?;
+            for i in 0..batch.num_rows() {
+                rows.push(values.iter().map(|column| column[i].clone()).collect());
+            }
+        }
+
+        Ok(Self { columns, rows })
+    }
+}
+
+fn arrow_values(array: &ArrayRef) -> Result<Vec<CellValue>> {
+    let values: Vec<CellValue> = match array.data_type() {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => {
+            let ints = cast(array, &DataType::Int64)?;
+            as_primitive_array::<Int64Type>(&ints)
+                .iter()
+                .map(|v| v.map(CellValue::Int).unwrap_or(CellValue::Null))
+                .collect()
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 | DataType::Decimal128(..) => {
+            let floats = cast(array, &DataType::Float64)?;
+            as_primitive_array::<Float64Type>(&floats)
+                .iter()
+                .map(|v| v.map(CellValue::Float).unwrap_or(CellValue::Null))
+                .collect()
+        }
+        DataType::Timestamp(..) => {
+            let micros = cast(array, &DataType::Timestamp(TimeUnit::Microsecond, None))?;
+            let micros = cast(&micros, &DataType::Int64)?;
+            as_primitive_array::<Int64Type>(&micros)
+                .iter()
+                .map(|v| match v {
+                    Some(t) => CellValue::Text(fmt_micros(t, "%Y-%m-%d %H:%M:%S")),
+                    None => CellValue::Null,
+                })
+                .collect()
+        }
+        DataType::Date32 => {
+            let days = cast(array, &DataType::Int32)?;
+            as_primitive_array::<Int32Type>(&days)
+                .iter()
+                .map(|v| match v {
+                    Some(d) => CellValue::Text(fmt_micros(d as i64 * 86_400_000_000, "%Y-%m-%d")),
+                    None => CellValue::Null,
+                })
+                .collect()
+        }
+        _ => (0..array.len())
+            .map(|i| {
+                if array.is_null(i) {
+                    Ok(CellValue::Null)
+                } else {
+                    Ok(CellValue::Text(array_value_to_string(array, i)?))
+                }
+            })
+            .collect::<Result<_>>()?,
+    };
+    Ok(values)
+}
+
+/// Maximum difference between two floats that are still considered equal.
+const FLOAT_EPSILON: f64 = 1e-6;
+
+/// Check that every engine returned the same rows, ignoring row order and column names.
+/// The first result is used as the reference. Prints a diff for every mismatch. Results with
+/// unreadable cells are reported and skipped, and count as a mismatch.
+pub fn compare_results(results: &[(&str, QueryResult)]) -> bool {
+    let (results, unreadable): (Vec<_>, Vec<_>) = results.iter().partition(|(_, result)| {
+        !result.rows.iter().flatten().any(|v| *v == CellValue::Unreadable)
+    });
+    for (engine, _) in &unreadable {
+        outln!("UNREADABLE: {engine} has columns that couldn't be read, not compared");
+    }
+    let Some((reference, rest)) = results.split_first() else {
+        if !unreadable.is_empty() {
+            outln!();
+        }
+        return unreadable.is_empty();
+    };
+    let (reference_engine, reference_rows) = (reference.0, sorted_rows(&reference.1));
+
+    let mut all_equal = true;
+    for (engine, result) in rest.iter().map(|r| (r.0, &r.1)) {
+        let rows = sorted_rows(result);
+        let equal = rows.len() == reference_rows.len()
+            && rows.iter().zip(&reference_rows).all(|(a, b)| rows_equal(a, b));
+        if equal {
+            continue;
+        }
+
+        all_equal = false;
+        outln!("MISMATCH: {engine} differs from {reference_engine}");
+        for i in 0..rows.len().max(reference_rows.len()) {
+            let (a, b) = (reference_rows.get(i), rows.get(i));
+            if let (Some(a), Some(b)) = (a, b) {
+                if rows_equal(a, b) {
+                    continue;
+                }
+            }
+            outln!("  row {i}:");
+            outln!("    {:<16} {}", reference_engine, fmt_row(a));
+            outln!("    {:<16} {}", engine, fmt_row(b));
+        }
+    }
+
+    if all_equal {
+        let engines: Vec<_> = results.iter().map(|(engine, _)| *engine).collect();
+        outln!("OK: {} returned the same rows", engines.join(", "));
+    }
+    outln!();
+    all_equal && unreadable.is_empty()
+}
+
+fn sorted_rows(result: &QueryResult) -> Vec<&Vec<CellValue>> {
+    let mut rows: Vec<_> = result.rows.iter().collect();
+    rows.sort_by(|a, b| {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| cmp_cells(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    });
+    rows
+}
+
+fn rows_equal(a: &[CellValue], b: &[CellValue]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| cells_equal(a, b))
+}
+
+fn cells_equal(a: &CellValue, b: &CellValue) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => (a - b).abs() <= FLOAT_EPSILON * a.abs().max(b.abs()).max(1.0),
+        _ => a == b,
+    }
+}
+
+/// Nulls first, then numbers, text and bytes.
+fn cmp_cells(a: &CellValue, b: &CellValue) -> Ordering {
+    fn rank(v: &CellValue) -> u8 {
+        match v {
+            CellValue::Null => 0,
+            CellValue::Int(_) | CellValue::Float(_) => 1,
+            CellValue::Text(_) => 2,
+            CellValue::Bytes(_) => 3,
+            CellValue::Unreadable => 4,
+        }
+    }
+
+    match (a, b) {
+        (CellValue::Text(a), CellValue::Text(b)) => a.cmp(b),
+        (CellValue::Bytes(a), CellValue::Bytes(b)) => a.cmp(b),
+        _ => match (a.as_f64(), b.as_f64()) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            _ => rank(a).cmp(&rank(b)),
+        },
+    }
+}
+
+fn fmt_row(row: Option<&&Vec<CellValue>>) -> String {
+    match row {
+        Some(row) => {
+            let cells: Vec<_> = row.iter().map(fmt_cell).collect();
+            format!("[{}]", cells.join(", "))
+        }
+        None => "<missing>".into(),
+    }
+}
+
+impl CellValue {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            CellValue::Int(n) => Some(*n as f64),
+            CellValue::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+fn print_result(result: &QueryResult, opts: &ExecOptions) {
+    if opts.quiet {
+        outln!("{} ({} rows)", result.columns.join(", "), result.rows.len());
+        return;
+    }
+    // CSV and JSON are meant for other tools, always print them in full.
+    let shown = match opts.max_print_rows {
+        Some(max) => result.rows.len().min(max),
+        None => result.rows.len(),
+    };
+    let visible = &result.rows[..shown];
+    let hidden = result.rows.len() - shown;
+
+    match opts.format {
+        OutputFormat::Table => {
+            let mut table = Table::new();
+            table
+                .load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_content_arrangement(ContentArrangement::Disabled)
+                .set_header(&result.columns);
+            for row in visible {
+                table.add_row(row.iter().map(|v| match opts.column_width {
+                    Some(width) => truncate(&fmt_cell(v), width),
+                    None => fmt_cell(v),
+                }));
+            }
+            for (i, column) in table.column_iter_mut().enumerate() {
+                let numeric = visible.iter().all(|row| {
+                    matches!(row[i], CellValue::Int(_) | CellValue::Float(_) | CellValue::Null)
+                });
+                if numeric && !visible.is_empty() {
+                    column.set_cell_alignment(CellAlignment::Right);
+                }
+            }
+            outln!("{table}");
+            print_hidden_rows(hidden);
+        }
+        OutputFormat::Plain => {
+            let rows: Vec<Vec<String>> = visible
+                .iter()
+                .map(|row| row.iter().map(fmt_cell).collect())
+                .collect();
+            let widths: Vec<usize> = match opts.column_width {
+                Some(width) => vec![width; result.columns.len()],
+                None => (0..result.columns.len())
+                    .map(|i| {
+                        rows.iter()
+                            .map(|row| row[i].chars().count())
+                            .chain([result.columns[i].chars().count()])
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect(),
+            };
+
+            print_column_names(&result.columns, &widths);
+            for row in &rows {
+                for (v, width) in row.iter().zip(&widths) {
+                    out!("| {:<width$} ", truncate(v, *width));
+                }
+                outln!("|");
+            }
+            print_divider(&widths);
+            print_hidden_rows(hidden);
+        }
+        OutputFormat::Csv => {
+            let header: Vec<_> = result.columns.iter().map(|c| csv_escape(c)).collect();
+            outln!("{}", header.join(","));
+            for row in &result.rows {
+                let line: Vec<_> = row
+                    .iter()
+                    .map(|v| match v {
+                        CellValue::Null | CellValue::Unreadable => String::new(),
+                        v => csv_escape(&fmt_cell(v)),
+                    })
+                    .collect();
+                outln!("{}", line.join(","));
+            }
+        }
+        OutputFormat::Json => {
+            for row in &result.rows {
+                let obj: serde_json::Map<_, _> = result
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(json_value))
+                    .collect();
+                outln!("{}", serde_json::Value::Object(obj));
+            }
+        }
+        OutputFormat::Markdown => {
+            let header: Vec<_> = result.columns.iter().map(|c| markdown_escape(c)).collect();
+            outln!("| {} |", header.join(" | "));
+            outln!("|{}", "---|".repeat(result.columns.len()));
+            for row in visible {
+                let line: Vec<_> = row.iter().map(|v| markdown_escape(&fmt_cell(v))).collect();
+                outln!("| {} |", line.join(" | "));
+            }
+            print_hidden_rows(hidden);
+            // Otherwise the timing line that follows would become a table row.
+            outln!();
+        }
+    }
 }
 
-fn print_divider(column_len: usize) {
-    for _ in 0..column_len {
-        print!("+{:-<22}", "");
+fn print_hidden_rows(hidden: usize) {
+    if hidden > 0 {
+        outln!("... ({hidden} more rows)");
     }
-    println!("+");
 }
 
-fn print_column_names<I>(names: &[I])
-where
-    I: std::fmt::Display,
-{
-    print_divider(names.len());
-    for column in names {
-        print!("| {:<20} ", column);
+fn markdown_escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
     }
-    println!("|");
-    print_divider(names.len());
 }
 
-fn fmt_sql_value(v: rusqlite::types::Value) -> String {
+fn json_value(v: &CellValue) -> serde_json::Value {
     match v {
-        rusqlite::types::Value::Null => "null".into(),
-        rusqlite::types::Value::Integer(n) => format!("{n}"),
-        rusqlite::types::Value::Real(n) => format!("{n}"),
-        rusqlite::types::Value::Text(t) => t,
-        rusqlite::types::Value::Blob(b) => format!("Blob(len={})", b.len()),
+        CellValue::Null => serde_json::Value::Null,
+        CellValue::Int(n) => (*n).into(),
+        CellValue::Float(n) => (*n).into(),
+        CellValue::Text(t) => t.as_str().into(),
+        CellValue::Bytes(_) | CellValue::Unreadable => fmt_cell(v).into(),
+    }
+}
+
+fn print_divider(widths: &[usize]) {
+    for width in widths {
+        out!("+{:-<1$}", "", width + 2);
+    }
+    outln!("+");
+}
+
+fn print_column_names(names: &[String], widths: &[usize]) {
+    print_divider(widths);
+    for (column, width) in names.iter().zip(widths) {
+        out!("| {:<width$} ", truncate(column, *width));
+    }
+    outln!("|");
+    print_divider(widths);
+}
+
+/// Cut the value to `width` characters ending with "...". Counts chars so multibyte
+/// characters are never split.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_string();
     }
+    let keep = width.saturating_sub(3);
+    let mut out: String = s.chars().take(keep).collect();
+    out.push_str(&"..."[..width - keep]);
+    out
 }
 
-fn fmt_duck_value(v: duckdb::types::Value) -> String {
+fn fmt_cell(v: &CellValue) -> String {
     match v {
-        duckdb::types::Value::Null => format!("null"),
-        duckdb::types::Value::Boolean(b) => format!("{b}"),
-        duckdb::types::Value::TinyInt(n) => format!("{n}"),
-        duckdb::types::Value::SmallInt(n) => format!("{n}"),
-        duckdb::types::Value::Int(n) => format!("{n}"),
-        duckdb::types::Value::BigInt(n) => format!("{n}"),
-        duckdb::types::Value::HugeInt(n) => format!("{n}"),
-        duckdb::types::Value::UTinyInt(n) => format!("{n}"),
-        duckdb::types::Value::USmallInt(n) => format!("{n}"),
-        duckdb::types::Value::UInt(n) => format!("{n}"),
-        duckdb::types::Value::UBigInt(n) => format!("{n}"),
-        duckdb::types::Value::Float(n) => format!("{n}"),
-        duckdb::types::Value::Double(n) => format!("{n}"),
-        duckdb::types::Value::Decimal(n) => format!("{n}"),
-        duckdb::types::Value::Timestamp(u, t) => format!("{t}{:?}", u),
-        duckdb::types::Value::Text(t) => t,
-        duckdb::types::Value::Blob(b) => format!("Blob(len={})", b.len()),
-        duckdb::types::Value::Date32(d) => format!("{d}"),
-        duckdb::types::Value::Time64(u, t) => format!("{t}{:?}", u),
+        CellValue::Null => "null".into(),
+        CellValue::Int(n) => format!("{n}"),
+        // Engines sum floats in different order, round away the noise.
+        CellValue::Float(n) => format!("{}", (n * 1e6).round() / 1e6),
+        CellValue::Text(t) => t.clone(),
+        CellValue::Bytes(len) => format!("Blob(len={len})"),
+        CellValue::Unreadable => String::new(),
+    }
+}
+
+impl From<rusqlite::types::Value> for CellValue {
+    fn from(v: rusqlite::types::Value) -> Self {
+        match v {
+            rusqlite::types::Value::Null => CellValue::Null,
+            rusqlite::types::Value::Integer(n) => CellValue::Int(n),
+            rusqlite::types::Value::Real(n) => CellValue::Float(n),
+            rusqlite::types::Value::Text(t) => CellValue::Text(t),
+            rusqlite::types::Value::Blob(b) => CellValue::Bytes(b.len()),
+        }
+    }
+}
+
+impl From<duckdb::types::Value> for CellValue {
+    fn from(v: duckdb::types::Value) -> Self {
+        match v {
+            duckdb::types::Value::Null => CellValue::Null,
+            duckdb::types::Value::Boolean(b) => CellValue::Int(b as i64),
+            duckdb::types::Value::TinyInt(n) => CellValue::Int(n.into()),
+            duckdb::types::Value::SmallInt(n) => CellValue::Int(n.into()),
+            duckdb::types::Value::Int(n) => CellValue::Int(n.into()),
+            duckdb::types::Value::BigInt(n) => CellValue::Int(n),
+            duckdb::types::Value::HugeInt(n) => match i64::try_from(n) {
+                Ok(n) => CellValue::Int(n),
+                Err(_) => CellValue::Float(n as f64),
+            },
+            duckdb::types::Value::UTinyInt(n) => CellValue::Int(n.into()),
+            duckdb::types::Value::USmallInt(n) => CellValue::Int(n.into()),
+            duckdb::types::Value::UInt(n) => CellValue::Int(n.into()),
+            duckdb::types::Value::UBigInt(n) => match i64::try_from(n) {
+                Ok(n) => CellValue::Int(n),
+                Err(_) => CellValue::Float(n as f64),
+            },
+            duckdb::types::Value::Float(n) => CellValue::Float(n.into()),
+            duckdb::types::Value::Double(n) => CellValue::Float(n),
+            duckdb::types::Value::Decimal(n) => match n.to_string().parse() {
+                Ok(n) => CellValue::Float(n),
+                Err(_) => CellValue::Text(n.to_string()),
+            },
+            duckdb::types::Value::Timestamp(u, t) => {
+                CellValue::Text(fmt_micros(u.to_micros(t), "%Y-%m-%d %H:%M:%S"))
+            }
+            duckdb::types::Value::Text(t) => CellValue::Text(t),
+            duckdb::types::Value::Blob(b) => CellValue::Bytes(b.len()),
+            duckdb::types::Value::Date32(d) => {
+                CellValue::Text(fmt_micros(d as i64 * 86_400_000_000, "%Y-%m-%d"))
+            }
+            duckdb::types::Value::Time64(u, t) => {
+                CellValue::Text(fmt_micros(u.to_micros(t), "%H:%M:%S"))
+            }
+        }
+    }
+}
+
+impl From<serde_json::Value> for CellValue {
+    fn from(v: serde_json::Value) -> Self {
+        match v {
+            serde_json::Value::Null => CellValue::Null,
+            serde_json::Value::Bool(b) => CellValue::Int(b as i64),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(n) => CellValue::Int(n),
+                None => CellValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(t) => CellValue::Text(t),
+            other => CellValue::Text(other.to_string()),
+        }
+    }
+}
+
+impl From<AnyValue<'_>> for CellValue {
+    fn from(v: AnyValue<'_>) -> Self {
+        match v {
+            AnyValue::Null => CellValue::Null,
+            AnyValue::Boolean(b) => CellValue::Int(b as i64),
+            AnyValue::Int8(n) => CellValue::Int(n.into()),
+            AnyValue::Int16(n) => CellValue::Int(n.into()),
+            AnyValue::Int32(n) => CellValue::Int(n.into()),
+            AnyValue::Int64(n) => CellValue::Int(n),
+            AnyValue::UInt8(n) => CellValue::Int(n.into()),
+            AnyValue::UInt16(n) => CellValue::Int(n.into()),
+            AnyValue::UInt32(n) => CellValue::Int(n.into()),
+            AnyValue::UInt64(n) => match i64::try_from(n) {
+                Ok(n) => CellValue::Int(n),
+                Err(_) => CellValue::Float(n as f64),
+            },
+            AnyValue::Float32(n) => CellValue::Float(n.into()),
+            AnyValue::Float64(n) => CellValue::Float(n),
+            AnyValue::Utf8(t) => CellValue::Text(t.to_string()),
+            AnyValue::Date(d) => {
+                CellValue::Text(fmt_micros(d as i64 * 86_400_000_000, "%Y-%m-%d"))
+            }
+            AnyValue::Datetime(t, unit, _) => {
+                let micros = match unit {
+                    polars::prelude::TimeUnit::Nanoseconds => t / 1000,
+                    polars::prelude::TimeUnit::Microseconds => t,
+                    polars::prelude::TimeUnit::Milliseconds => t * 1000,
+                };
+                CellValue::Text(fmt_micros(micros, "%Y-%m-%d %H:%M:%S"))
+            }
+            other => CellValue::Text(other.to_string()),
+        }
+    }
+}
+
+/// Format microseconds since the Unix epoch.
+fn fmt_micros(micros: i64, fmt: &str) -> String {
+    let secs = micros.div_euclid(1_000_000);
+    let nanos = (micros.rem_euclid(1_000_000) * 1000) as u32;
+    match chrono::NaiveDateTime::from_timestamp_opt(secs, nanos) {
+        Some(t) => t.format(fmt).to_string(),
+        None => format!("{micros}us"),
     }
 }
@@ -0,0 +1,64 @@
+use std::iter;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use compare_olap_rust::{common, generate, generate_normalized, query};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Generate events and compare OLAP engines on them.
+#[derive(Parser)]
+#[command(name = "compare-olap")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate random events into SQLite, DuckDB and Parquet, same as `gen_data`.
+    Generate(generate::Args),
+    /// Generate random events into SQLite with a normalized schema, same as
+    /// `gen_data_normalized`.
+    GenerateNormalized(generate_normalized::Args),
+    /// Run analytical queries against every engine, same as `queries`.
+    Query(query::Args),
+    /// Generate events and query them in one process.
+    All(AllArgs),
+}
+
+#[derive(clap::Args)]
+struct AllArgs {
+    #[command(flatten)]
+    generate: generate::Args,
+
+    /// Options for `query` after `--`, e.g. `-- --only top_pages`. The data directory is
+    /// the one events are generated into.
+    #[arg(last = true)]
+    query: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    common::init_tracing();
+
+    match Cli::parse().command {
+        Command::Generate(args) => generate::run(&args),
+        Command::GenerateNormalized(args) => generate_normalized::run(&args),
+        Command::Query(args) => query::run(&args, None).await,
+        Command::All(args) => {
+            // Parse the query options first so that a typo doesn't waste a generator run.
+            let argv = iter::once(String::from("compare-olap all --")).chain(args.query);
+            let mut query_args = query::Args::parse_from(argv);
+            query_args.data_dir = args.generate.data_dir.clone();
+
+            let Some(databases) = generate::generate(&args.generate)? else {
+                return Ok(());
+            };
+            let databases = args.generate.in_memory.then_some(databases);
+            query::run(&query_args, databases).await
+        }
+    }
+}
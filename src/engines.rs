@@ -0,0 +1,240 @@
+//! Backends behind a common interface so that `queries` can run a benchmark on each of them
+//! the same way.
+
+use std::future::Future;
+
+use anyhow::Result;
+use datafusion::prelude::SessionContext;
+use polars::prelude::LazyFrame;
+
+use crate::{
+    benchmarks::Benchmark,
+    common::{
+        exec_df, exec_duck, exec_duck_parquet, exec_duck_typed, exec_polars, exec_polars_sql,
+        exec_sqlite, exec_sqlite_normalized, explain_df, explain_duck, explain_polars,
+        explain_sqlite, Engine, ExecOptions, QueryResult,
+    },
+};
+
+/// An opened engine that runs its variant of each benchmark.
+///
+/// Benchmarks carry SQL per engine (and a Polars expression), so `run` takes the benchmark
+/// and picks the variant itself. Engines without a variant return `None` and are skipped.
+/// Engines are `Send` so that `--parallel-engines` can run each of them on its own thread.
+pub trait QueryEngine: Send {
+    fn engine(&self) -> Engine;
+
+    fn name(&self) -> &'static str {
+        self.engine().label()
+    }
+
+    /// Run, print and record the benchmark's query for this engine.
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>>;
+
+    /// Query plan for `--explain`, only Polars and DataFusion print one.
+    fn explain(&self, _bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        None
+    }
+
+    /// Query plan saved to `PLAN_OUTPUT`. Same as [`QueryEngine::explain`] unless the engine
+    /// has a plan that isn't printed by `--explain`.
+    fn plan(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<String>> {
+        self.explain(bench, opts)
+    }
+}
+
+/// Async engines run on the `queries` tokio runtime. `block_in_place` lets the current worker
+/// block while DataFusion and Postgres still spawn their own tasks.
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
+
+pub struct Sqlite(pub rusqlite::Connection);
+
+impl QueryEngine for Sqlite {
+    fn engine(&self) -> Engine {
+        Engine::Sqlite
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.sqlite_sql()?;
+        Some(exec_sqlite(&self.0, bench.name(), sql, opts))
+    }
+
+    fn plan(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        Some(explain_sqlite(&self.0, bench.sqlite_sql()?))
+    }
+}
+
+/// Normalized schema from `gen_data_normalized`.
+pub struct SqliteNormalized(pub rusqlite::Connection);
+
+impl QueryEngine for SqliteNormalized {
+    fn engine(&self) -> Engine {
+        Engine::SqliteNormalized
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.sqlite_normalized_sql()?;
+        Some(exec_sqlite_normalized(&self.0, bench.name(), sql, opts))
+    }
+
+    fn plan(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        Some(explain_sqlite(&self.0, bench.sqlite_normalized_sql()?))
+    }
+}
+
+/// DuckDB with a JSON payload.
+pub struct Duck(pub duckdb::Connection);
+
+impl QueryEngine for Duck {
+    fn engine(&self) -> Engine {
+        Engine::Duck
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.duck_sql()?;
+        Some(exec_duck(&self.0, bench.name(), sql, bench.duck_columns(), opts))
+    }
+
+    fn plan(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        Some(explain_duck(&self.0, bench.duck_sql()?))
+    }
+}
+
+/// DuckDB with a struct payload.
+pub struct DuckTyped(pub duckdb::Connection);
+
+impl QueryEngine for DuckTyped {
+    fn engine(&self) -> Engine {
+        Engine::DuckTyped
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.duck_typed_sql()?;
+        Some(exec_duck_typed(&self.0, bench.name(), sql, bench.duck_columns(), opts))
+    }
+
+    fn plan(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        Some(explain_duck(&self.0, bench.duck_typed_sql()?))
+    }
+}
+
+/// In-memory DuckDB over the parquet file, runs the typed queries.
+pub struct DuckParquet(pub duckdb::Connection);
+
+impl QueryEngine for DuckParquet {
+    fn engine(&self) -> Engine {
+        Engine::DuckParquet
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.duck_typed_sql()?;
+        Some(exec_duck_parquet(&self.0, bench.name(), sql, bench.duck_columns(), opts))
+    }
+
+    fn plan(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        Some(explain_duck(&self.0, bench.duck_typed_sql()?))
+    }
+}
+
+/// Polars expression API over the `events` LazyFrame.
+pub struct Polars(pub LazyFrame);
+
+impl QueryEngine for Polars {
+    fn engine(&self) -> Engine {
+        Engine::Polars
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let lf = bench.polars(self.0.clone())?;
+        Some(exec_polars(bench.name(), lf, opts))
+    }
+
+    fn explain(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<String>> {
+        let lf = bench.polars(self.0.clone())?;
+        Some(explain_polars(lf, opts))
+    }
+}
+
+/// Polars SQL interface over the same LazyFrame as [`Polars`].
+pub struct PolarsSql(pub LazyFrame);
+
+impl QueryEngine for PolarsSql {
+    fn engine(&self) -> Engine {
+        Engine::PolarsSql
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.polars_sql()?;
+        Some(exec_polars_sql(bench.name(), self.0.clone(), sql, opts))
+    }
+}
+
+pub struct DataFusion(pub SessionContext);
+
+impl QueryEngine for DataFusion {
+    fn engine(&self) -> Engine {
+        Engine::DataFusion
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.datafusion_sql()?;
+        Some(block_on(exec_df(&self.0, bench.name(), sql, opts)))
+    }
+
+    fn explain(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        let sql = bench.datafusion_sql()?;
+        Some(block_on(explain_df(&self.0, sql)))
+    }
+}
+
+#[cfg(feature = "clickhouse")]
+pub struct ClickHouse(pub chdb_rust::session::Session);
+
+#[cfg(feature = "clickhouse")]
+impl QueryEngine for ClickHouse {
+    fn engine(&self) -> Engine {
+        Engine::ClickHouse
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.clickhouse_sql()?;
+        Some(crate::common::exec_clickhouse(&self.0, bench.name(), sql, opts))
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub struct Postgres(pub tokio_postgres::Client);
+
+#[cfg(feature = "postgres")]
+impl QueryEngine for Postgres {
+    fn engine(&self) -> Engine {
+        Engine::Postgres
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.postgres_sql()?;
+        Some(block_on(crate::common::exec_postgres(&self.0, bench.name(), sql, opts)))
+    }
+}
+
+/// SQLite with the payload stored as MessagePack, fields are read with `msgpack_get`.
+#[cfg(feature = "msgpack")]
+pub struct SqliteMsgpack(pub rusqlite::Connection);
+
+#[cfg(feature = "msgpack")]
+impl QueryEngine for SqliteMsgpack {
+    fn engine(&self) -> Engine {
+        Engine::SqliteMsgpack
+    }
+
+    fn run(&self, bench: &dyn Benchmark, opts: &ExecOptions) -> Option<Result<QueryResult>> {
+        let sql = bench.sqlite_msgpack_sql()?;
+        Some(crate::common::exec_sqlite_msgpack(&self.0, bench.name(), sql, opts))
+    }
+
+    fn plan(&self, bench: &dyn Benchmark, _opts: &ExecOptions) -> Option<Result<String>> {
+        Some(explain_sqlite(&self.0, bench.sqlite_msgpack_sql()?))
+    }
+}
@@ -0,0 +1,777 @@
+//! Event model and random session generation shared by the data generators.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use serde_json::json;
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: String,
+    pub session_id: String,
+    pub page_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub payload: EventPayload,
+}
+
+impl Event {
+    /// Value of the `event_type` column.
+    pub fn event_type(&self) -> &'static str {
+        self.payload.event_type()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EventPayload {
+    PageLoad { path: String, user_agent: String },
+    ChatMessage { text: String },
+    Feedback { score: i32 },
+    ContactUs { name: String, email: String },
+}
+
+impl EventPayload {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            EventPayload::PageLoad { .. } => "page_load",
+            EventPayload::ChatMessage { .. } => "chat_message",
+            EventPayload::Feedback { .. } | EventPayload::ContactUs { .. } => "form_submit",
+        }
+    }
+
+    pub fn form_type(&self) -> Option<&'static str> {
+        match self {
+            EventPayload::Feedback { .. } => Some("feedback"),
+            EventPayload::ContactUs { .. } => Some("contact-us"),
+            _ => None,
+        }
+    }
+
+    /// Score of a feedback form.
+    pub fn score(&self) -> Option<i32> {
+        match self {
+            EventPayload::Feedback { score } => Some(*score),
+            _ => None,
+        }
+    }
+
+    /// Write the `payload` JSON into `buf`, replacing its contents. Produces the same bytes as
+    /// `to_json().to_string()` without building a `serde_json::Value`, reuse `buf` across events.
+    pub fn write_json(&self, buf: &mut String) {
+        let mut bytes = std::mem::take(buf).into_bytes();
+        bytes.clear();
+        serde_json::to_writer(&mut bytes, self).expect("Payload is always serializable");
+        *buf = String::from_utf8(bytes).expect("serde_json writes UTF-8");
+    }
+
+    /// Parse a `payload` column value back, e.g. from an imported file. Only the payloads
+    /// produced by the generator are supported.
+    pub fn from_json(event_type: &str, payload: &serde_json::Value) -> Result<Self> {
+        let field = |key: &str| -> Result<String> {
+            payload
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .with_context(|| format!("{event_type} payload is missing {key}"))
+        };
+        let form_field = |name: &str| -> Result<String> {
+            payload
+                .get("fields")
+                .and_then(|fields| fields.as_array())
+                .and_then(|fields| fields.iter().find(|f| f["name"] == name))
+                .and_then(|f| f["value"].as_str())
+                .map(String::from)
+                .with_context(|| format!("Form payload is missing field {name}"))
+        };
+
+        let form_type = payload.get("form_type").and_then(|v| v.as_str());
+        Ok(match (event_type, form_type) {
+            ("page_load", _) => EventPayload::PageLoad {
+                path: field("path")?,
+                user_agent: field("user_agent")?,
+            },
+            ("chat_message", _) => EventPayload::ChatMessage {
+                text: field("text")?,
+            },
+            ("form_submit", Some("feedback")) => EventPayload::Feedback {
+                score: form_field("score")?.parse().context("Invalid feedback score")?,
+            },
+            ("form_submit", Some("contact-us")) => EventPayload::ContactUs {
+                name: form_field("name")?,
+                email: form_field("email")?,
+            },
+            _ => bail!("Unsupported event type {event_type} with form type {form_type:?}"),
+        })
+    }
+
+    /// JSON stored in the `payload` column.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            EventPayload::PageLoad { path, user_agent } => json!({
+                "path": path,
+                "user_agent": user_agent,
+            }),
+            EventPayload::ChatMessage { text } => json!({
+                "text": text,
+            }),
+            EventPayload::Feedback { score } => json!({
+                "form_type": "feedback",
+                "fields": [{
+                    "name": "score",
+                    "value": format!("{score}"),
+                }],
+            }),
+            EventPayload::ContactUs { name, email } => json!({
+                "form_type": "contact-us",
+                "fields": [{
+                    "name": "name",
+                    "value": name,
+                }, {
+                    "name": "email",
+                    "value": email,
+                }],
+            }),
+        }
+    }
+}
+
+/// Serializes the same JSON as [`EventPayload::to_json`]. Keys are written in alphabetical
+/// order since that's how `serde_json::Value` objects are ordered.
+impl Serialize for EventPayload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            EventPayload::PageLoad { path, user_agent } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("user_agent", user_agent)?;
+                map.end()
+            }
+            EventPayload::ChatMessage { text } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("text", text)?;
+                map.end()
+            }
+            EventPayload::Feedback { score } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("fields", &[FormField("score", score)])?;
+                map.serialize_entry("form_type", "feedback")?;
+                map.end()
+            }
+            EventPayload::ContactUs { name, email } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry(
+                    "fields",
+                    &[FormField("name", name), FormField("email", email)],
+                )?;
+                map.serialize_entry("form_type", "contact-us")?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// `{"name": ..., "value": ...}` entry of form `fields`, values are always strings.
+struct FormField<'a, V>(&'a str, V);
+
+impl<V: fmt::Display> Serialize for FormField<'_, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct AsString<'a, V>(&'a V);
+
+        impl<V: fmt::Display> Serialize for AsString<'_, V> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self.0)
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("name", self.0)?;
+        map.serialize_entry("value", &AsString(&self.1))?;
+        map.end()
+    }
+}
+
+/// Generate all events of a single session.
+pub fn generate_session(ctx: &Ctx, rng: &mut impl Rng, timestamp: DateTime<Utc>) -> Vec<Event> {
+    let page_loads = ctx.page_loads[ctx.page_load_dist.sample(rng)];
+
+    let session_id = random_uuid(rng);
+    let mut events = vec![];
+    let mut previous: Option<Event> = None;
+
+    for _ in 0..page_loads {
+        let page_load = generate_page_load(ctx, rng, &session_id, previous.as_ref(), timestamp);
+        events.push(page_load.clone());
+
+        let mut forms = 0;
+
+        // Up to 20 events per page
+        let page_events = rng.gen_range(0..20);
+        for _ in 0..page_events {
+            let event = generate_event(ctx, rng, &page_load, timestamp);
+            // We only want 1-2 form submissions per page max.
+            if event.payload.form_type().is_some() {
+                forms += 1;
+                if forms > 1 {
+                    continue;
+                }
+            }
+
+            events.push(event);
+        }
+        previous = Some(page_load);
+    }
+
+    events
+}
+
+/// Random v4 UUID drawn from `rng` so that seeded runs are reproducible.
+pub fn random_uuid(rng: &mut impl Rng) -> String {
+    uuid::Builder::from_random_bytes(rng.gen())
+        .into_uuid()
+        .to_string()
+}
+
+/// Page load of a session. With journeys configured the path depends on the `previous` page
+/// load of the session, otherwise paths are picked uniformly.
+pub fn generate_page_load(
+    ctx: &Ctx,
+    rng: &mut impl Rng,
+    session_id: &str,
+    previous: Option<&Event>,
+    timestamp: DateTime<Utc>,
+) -> Event {
+    let id = random_uuid(rng);
+    let previous_path = previous.and_then(|page| match &page.payload {
+        EventPayload::PageLoad { path, .. } => path.strip_prefix('/'),
+        _ => None,
+    });
+    let path = ctx.next_path(rng, previous_path);
+    let page_id = random_uuid(rng);
+
+    Event {
+        id,
+        session_id: session_id.into(),
+        page_id,
+        timestamp,
+        payload: EventPayload::PageLoad {
+            path: format!("/{path}"),
+            user_agent: ctx.random_browser(rng).to_string(),
+        },
+    }
+}
+
+pub fn generate_event(
+    ctx: &Ctx,
+    rng: &mut impl Rng,
+    page: &Event,
+    timestamp: DateTime<Utc>,
+) -> Event {
+    let id = random_uuid(rng);
+    let session_id = page.session_id.to_string();
+    let page_id = page.page_id.to_string();
+
+    // A random number [0, 1)
+    let chance: f32 = rng.gen();
+    if chance < ctx.chat_message_below {
+        let text = ctx.random_text(rng);
+
+        Event {
+            id,
+            session_id,
+            page_id,
+            timestamp,
+            payload: EventPayload::ChatMessage { text },
+        }
+    } else if chance < ctx.contact_us_below {
+        let email = format!("{}@{}", ctx.random_word(rng), ctx.random_word(rng));
+
+        Event {
+            id,
+            session_id,
+            page_id,
+            timestamp,
+            payload: EventPayload::ContactUs {
+                name: ctx.random_word(rng).to_string(),
+                email,
+            },
+        }
+    } else {
+        let score = rng.gen_range(0..=100);
+
+        Event {
+            id,
+            session_id,
+            page_id,
+            timestamp,
+            payload: EventPayload::Feedback { score },
+        }
+    }
+}
+
+/// Weights of the generated data, loaded from a JSON file passed with `--config`.
+///
+/// ```json
+/// {
+///   "events": { "chat_message": 0.7, "contact_us": 0.15, "feedback": 0.15 },
+///   "page_loads": { "1": 0.4, "2": 0.3, "4": 0.2, "8": 0.08, "12": 0.02 }
+/// }
+/// ```
+///
+/// Both sections are optional and default to the values above. Probabilities in each section
+/// must sum to 1.0.
+///
+/// An optional `journeys` section makes sessions follow a Markov chain of paths instead of
+/// picking each path uniformly:
+///
+/// ```json
+/// {
+///   "journeys": {
+///     "start": { "home": 0.8, "search": 0.2 },
+///     "next": {
+///       "home": { "product": 0.6, "search": 0.4 },
+///       "search": { "product": 0.9, "home": 0.1 },
+///       "product": { "checkout": 0.3, "home": 0.7 }
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Probability of each kind of event on a page.
+    pub events: EventWeights,
+    /// Probability of a session having that many page loads.
+    pub page_loads: BTreeMap<usize, f64>,
+    /// Weighted path transitions, `None` picks paths uniformly.
+    pub journeys: Option<Journeys>,
+}
+
+/// Paths are given without the leading `/`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Journeys {
+    /// Probability of the first path of a session.
+    pub start: BTreeMap<String, f64>,
+    /// Probability of the next path given the previous one. Paths without transitions start
+    /// over from `start`.
+    #[serde(default)]
+    pub next: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventWeights {
+    #[serde(default)]
+    pub chat_message: f64,
+    #[serde(default)]
+    pub contact_us: f64,
+    #[serde(default)]
+    pub feedback: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            events: EventWeights {
+                chat_message: 0.7,
+                contact_us: 0.15,
+                feedback: 0.15,
+            },
+            page_loads: BTreeMap::from([(1, 0.4), (2, 0.3), (4, 0.2), (8, 0.08), (12, 0.02)]),
+            journeys: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        let config: Config = serde_json::from_str(&data)
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let events = &self.events;
+        check_probabilities("events", &[events.chat_message, events.contact_us, events.feedback])?;
+        let page_loads: Vec<_> = self.page_loads.values().copied().collect();
+        check_probabilities("page_loads", &page_loads)?;
+        if let Some(journeys) = &self.journeys {
+            let start: Vec<_> = journeys.start.values().copied().collect();
+            check_probabilities("journeys.start", &start)?;
+            for (from, to) in &journeys.next {
+                let next: Vec<_> = to.values().copied().collect();
+                check_probabilities(&format!("journeys.next.{from}"), &next)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_probabilities(section: &str, values: &[f64]) -> Result<()> {
+    if values.iter().any(|p| !(0.0..=1.0).contains(p)) {
+        bail!("Probabilities in {section} must be between 0 and 1, got {values:?}");
+    }
+    let sum: f64 = values.iter().sum();
+    if (sum - 1.0).abs() > 1e-6 {
+        bail!("Probabilities in {section} must sum to 1.0, got {sum}");
+    }
+    Ok(())
+}
+
+/// Paths are drawn from this many first words.
+const PATH_WORDS: usize = 40;
+
+/// Weighted choice of a path.
+struct PathChoice {
+    paths: Vec<String>,
+    dist: WeightedIndex<f64>,
+}
+
+impl PathChoice {
+    fn new(weights: &BTreeMap<String, f64>) -> Self {
+        Self {
+            paths: weights.keys().cloned().collect(),
+            dist: WeightedIndex::new(weights.values()).unwrap(),
+        }
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> &str {
+        &self.paths[self.dist.sample(rng)]
+    }
+}
+
+/// [`Journeys`] prepared for sampling.
+struct PathChain {
+    start: PathChoice,
+    next: HashMap<String, PathChoice>,
+}
+
+/// Word lists and weights random values are drawn from.
+pub struct Ctx {
+    words: Vec<String>,
+    browsers: Vec<String>,
+    page_loads: Vec<usize>,
+    page_load_dist: WeightedIndex<f64>,
+    journeys: Option<PathChain>,
+    // Upper bounds of a [0, 1) random number for picking the event kind.
+    chat_message_below: f32,
+    contact_us_below: f32,
+}
+
+impl Ctx {
+    pub fn new() -> Self {
+        Self::with_config(&Config::default())
+    }
+
+    /// Expects a validated config.
+    pub fn with_config(config: &Config) -> Self {
+        let events = &config.events;
+        Self {
+            words: parse_list(WORDS),
+            browsers: parse_list(BROWSERS),
+            page_loads: config.page_loads.keys().copied().collect(),
+            page_load_dist: WeightedIndex::new(config.page_loads.values()).unwrap(),
+            journeys: config.journeys.as_ref().map(|journeys| PathChain {
+                start: PathChoice::new(&journeys.start),
+                next: journeys
+                    .next
+                    .iter()
+                    .map(|(from, to)| (from.clone(), PathChoice::new(to)))
+                    .collect(),
+            }),
+            chat_message_below: events.chat_message as f32,
+            contact_us_below: (events.chat_message + events.contact_us) as f32,
+        }
+    }
+
+    /// Replace the embedded words used for paths, texts and form values.
+    /// The first 40 words are used for paths.
+    pub fn with_words(mut self, words: Vec<String>) -> Self {
+        self.words = words;
+        self
+    }
+
+    /// Replace the embedded user agents of page loads.
+    pub fn with_user_agents(mut self, user_agents: Vec<String>) -> Self {
+        self.browsers = user_agents;
+        self
+    }
+
+    pub fn random_path(&self, rng: &mut impl Rng) -> &str {
+        let index = rng.gen_range(0..self.words.len().min(PATH_WORDS));
+        &self.words[index]
+    }
+
+    /// Path of the next page load. Follows the configured journeys from `previous`, the path of
+    /// the previous page load in the session without the leading `/`.
+    pub fn next_path(&self, rng: &mut impl Rng, previous: Option<&str>) -> &str {
+        let Some(chain) = &self.journeys else {
+            return self.random_path(rng);
+        };
+        match previous.and_then(|path| chain.next.get(path)) {
+            Some(next) => next.sample(rng),
+            None => chain.start.sample(rng),
+        }
+    }
+
+    pub fn random_word(&self, rng: &mut impl Rng) -> &str {
+        let index = rng.gen_range(0..self.words.len());
+        &self.words[index]
+    }
+
+    pub fn random_text(&self, rng: &mut impl Rng) -> String {
+        let words = rng.gen_range(1..30);
+        (0..words)
+            .map(|_| self.random_word(rng))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn random_browser(&self, rng: &mut impl Rng) -> &str {
+        let index = rng.gen_range(0..self.browsers.len());
+        &self.browsers[index]
+    }
+}
+
+/// Load a word or user agent list from a file with one entry per line.
+pub fn load_list(path: &Path) -> Result<Vec<String>> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read list {}", path.display()))?;
+    let list = parse_list(&data);
+    if list.is_empty() {
+        bail!("List {} is empty", path.display());
+    }
+    Ok(list)
+}
+
+/// One entry per line, blank lines are skipped.
+fn parse_list(data: &str) -> Vec<String> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+const BROWSERS: &str = r#"
+Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.4 Safari/605.1.15
+Mozilla/5.0 (Macintosh; Intel Mac OS X x.y; rv:42.0) Gecko/20100101 Firefox/42.0
+Mozilla/5.0 (Windows NT 6.1; Win64; x64; rv:47.0) Gecko/20100101 Firefox/47.0
+Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/51.0.2704.103 Safari/537.36
+Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/51.0.2704.106 Safari/537.36 OPR/38.0.2220.41
+Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36 Edg/91.0.864.59
+Mozilla/5.0 (iPhone; CPU iPhone OS 13_5_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/13.1.1 Mobile/15E148 Safari/604.1
+Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:109.0) Gecko/20100101 Firefox/111.0
+Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)
+curl/7.64.1"#;
+
+// 200 most common words
+const WORDS: &str = r#"water
+away
+good
+want
+over
+how
+did
+man
+going
+where
+would
+or
+took
+school
+think
+home
+who
+didn’t
+ran
+know
+bear
+can’t
+again
+cat
+long
+things
+new
+after
+wanted
+eat
+everyone
+our
+two
+has
+yes
+play
+take
+thought
+dog
+well
+find
+more
+I’ll
+round
+tree
+magic
+shouted
+us
+other
+food
+fox
+through
+way
+been
+stop
+must
+red
+door
+right
+sea
+these
+began
+boy
+animals
+never
+next
+first
+work
+lots
+need
+that’s
+baby
+fish
+gave
+mouse
+something
+bed
+may
+still
+found
+live
+say
+soon
+night
+narrator
+small
+car
+couldn’t
+three
+head
+king
+town
+I’ve
+around
+every
+garden
+fast
+only
+many
+laughed
+let’s
+much
+suddenly
+told
+another
+great
+why
+cried
+keep
+room
+last
+jumped
+because
+even
+am
+before
+gran
+clothes
+tell
+key
+fun
+place
+mother
+sat
+boat
+window
+sleep
+feet
+morning
+queen
+each
+book
+its
+green
+different
+let
+girl
+which
+inside
+run
+any
+under
+hat
+snow
+air
+trees
+bad
+tea
+top
+eyes
+fell
+friends
+box
+dark
+grandad
+there’s
+looking
+end
+than
+best
+better
+hot
+sun
+across
+gone
+hard
+floppy
+really
+wind
+wish
+eggs
+once
+please
+thing
+stopped
+ever
+miss
+most
+cold
+park
+lived
+birds
+duck
+horse
+rabbit
+white
+coming
+he’s
+river
+liked
+giant
+looks
+use
+along
+plants
+dragon
+pulled
+we’re
+fly
+grow"#;
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, path::PathBuf, process, time::Instant};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use clap::Parser;
+use compare_olap_rust::{
+    common::{self, TimestampFormat},
+    events::{Event, EventPayload},
+    writers::{
+        start_writers, Compression, PartitionBy, TypedPayload, WriteOptions, DEFAULT_BATCH_SIZE,
+    },
+};
+
+/// Load events from a CSV file into SQLite, DuckDB and Parquet, the same outputs as `gen_data`.
+#[derive(Parser)]
+struct Args {
+    /// CSV file with a header row and a column for each `events` column.
+    csv: PathBuf,
+
+    /// Read an `events` column from a differently named CSV column, e.g.
+    /// `--column timestamp=created_at`. Can be repeated.
+    #[arg(long = "column", value_name = "EVENTS_COLUMN=CSV_COLUMN", value_parser = parse_mapping)]
+    columns: Vec<(String, String)>,
+
+    /// Field delimiter of the CSV file.
+    #[arg(long, default_value_t = ',')]
+    delimiter: char,
+
+    /// Also write events as newline delimited JSON to `events.ndjson`.
+    #[arg(long)]
+    ndjson: bool,
+
+    /// Also write events with the typed payload to an Arrow IPC (Feather) file
+    /// `events-typed.arrow`.
+    #[arg(long)]
+    arrow: bool,
+
+    /// Compress the NDJSON export (appends `.gz`/`.zst`) and Parquet/Arrow files
+    /// (using their native codecs, the file names are kept).
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+
+    /// Schema of the typed payload in DuckDB (Typed), Parquet and Arrow. `scored` adds an
+    /// integer `score` of feedback forms next to the string fields.
+    #[arg(long, value_enum, default_value_t = TypedPayload::Strings)]
+    typed_payload: TypedPayload,
+
+    /// Write a Hive-style partitioned Parquet dataset `events-typed/date=YYYY-MM-DD/` instead
+    /// of `events-typed.parquet`. Read it with `queries --parquet-partitioned`.
+    #[arg(long, value_enum)]
+    parquet_partition_by: Option<PartitionBy>,
+
+    /// Rows per Parquet row group and Arrow record batch.
+    #[arg(
+        long,
+        default_value_t = DEFAULT_BATCH_SIZE,
+        value_parser = clap::value_parser!(u64).range(1..).map(|n| n as usize)
+    )]
+    batch_size: usize,
+
+    /// Add events to existing databases instead of failing when the `events` table exists.
+    #[arg(long)]
+    append: bool,
+
+    /// How many times a SQLite insert is retried with exponential backoff when the database
+    /// is locked.
+    #[arg(long, default_value_t = 5)]
+    busy_retries: u32,
+
+    /// How to store timestamps. `native` uses each store's timestamp type.
+    #[arg(long, value_enum, default_value_t = TimestampFormat::Native)]
+    timestamps: TimestampFormat,
+
+    /// Directory for the databases and files. Created if it doesn't exist.
+    #[arg(long, env = "COMPARE_OLAP_DATA_DIR", default_value = ".")]
+    data_dir: PathBuf,
+}
+
+/// Columns of the `events` table, all of them must be present in the CSV file.
+const COLUMNS: [&str; 6] = ["id", "session_id", "page_id", "timestamp", "event_type", "payload"];
+
+fn parse_mapping(value: &str) -> Result<(String, String), String> {
+    let Some((column, csv_column)) = value.split_once('=') else {
+        return Err(format!("expected EVENTS_COLUMN=CSV_COLUMN, got {value}"));
+    };
+    if !COLUMNS.contains(&column) {
+        return Err(format!("unknown events column {column}, expected one of {COLUMNS:?}"));
+    }
+    Ok((column.to_string(), csv_column.to_string()))
+}
+
+fn main() -> Result<()> {
+    common::init_tracing();
+
+    let args = Args::parse();
+    if !args.delimiter.is_ascii() {
+        bail!("Delimiter must be an ASCII character, got {}", args.delimiter);
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(args.delimiter as u8)
+        .from_path(&args.csv)
+        .with_context(|| format!("Failed to open {}", args.csv.display()))?;
+
+    // Check the header before any database is touched.
+    let mapping: HashMap<_, _> = args.columns.iter().cloned().collect();
+    let header = reader.headers()?.clone();
+    let mut indexes = [0; COLUMNS.len()];
+    let mut missing = vec![];
+    for (i, column) in COLUMNS.iter().enumerate() {
+        let csv_column = mapping.get(*column).map_or(*column, String::as_str);
+        match header.iter().position(|name| name == csv_column) {
+            Some(index) => indexes[i] = index,
+            None => missing.push(csv_column),
+        }
+    }
+    if !missing.is_empty() {
+        eprintln!("{} is missing columns: {}", args.csv.display(), missing.join(", "));
+        eprintln!("Found: {}", header.iter().collect::<Vec<_>>().join(", "));
+        eprintln!("Map differently named columns with --column EVENTS_COLUMN=CSV_COLUMN");
+        process::exit(1);
+    }
+
+    let opts = common::ExecOptions::from_env()?;
+    let data_dir = common::DataDir::create(&args.data_dir)?;
+    let options = WriteOptions {
+        data_dir,
+        timestamps: args.timestamps,
+        append: args.append,
+        busy_retries: args.busy_retries,
+        batch_size: args.batch_size,
+        compress: args.compress,
+        ndjson: args.ndjson,
+        arrow: args.arrow,
+        in_memory: false,
+        typed_payload: args.typed_payload,
+        parquet_partition_by: args.parquet_partition_by,
+    };
+    let writers = start_writers(&options, opts)?;
+
+    let started = Instant::now();
+    let mut rows = 0;
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |pos| pos.line());
+        let event = parse_event(&record, &indexes)
+            .with_context(|| format!("Invalid row on line {line} of {}", args.csv.display()))?;
+        writers.send(event);
+
+        rows += 1;
+        if rows % 100_000 == 0 {
+            tracing::info!("Read {rows} rows");
+        }
+    }
+    tracing::info!("Read {rows} rows in {:?}", started.elapsed());
+
+    writers.finish()?;
+    common::report_sizes(&options.files());
+
+    tracing::info!("Done.");
+    Ok(())
+}
+
+/// Build an event from a CSV record, `indexes` are the positions of [`COLUMNS`].
+fn parse_event(record: &csv::StringRecord, indexes: &[usize; COLUMNS.len()]) -> Result<Event> {
+    let [id, session_id, page_id, timestamp, event_type, payload] =
+        indexes.map(|i| record.get(i).unwrap_or_default());
+    let payload: serde_json::Value = serde_json::from_str(payload).context("Invalid payload")?;
+
+    Ok(Event {
+        id: id.to_string(),
+        session_id: session_id.to_string(),
+        page_id: page_id.to_string(),
+        timestamp: parse_timestamp(timestamp)?,
+        payload: EventPayload::from_json(event_type, &payload)?,
+    })
+}
+
+/// RFC 3339, `YYYY-MM-DD HH:MM:SS[.fff]` in UTC or epoch milliseconds.
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(Utc.from_utc_datetime(&ts));
+        }
+    }
+    if let Some(ts) = value.parse().ok().and_then(|ms| Utc.timestamp_millis_opt(ms).single()) {
+        return Ok(ts);
+    }
+    bail!("Invalid timestamp {value}")
+}
@@ -0,0 +1,440 @@
+//! Random event generator behind `gen_data` and `compare-olap generate`.
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{mpsc::Receiver, Arc},
+    thread,
+};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use clap::Parser;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    common::{self, TimestampFormat},
+    events::{generate_session, load_list, Config, Ctx, Event},
+    writers::{
+        bench_inserts, start_writers, Compression, Databases, PartitionBy, TypedPayload,
+        WriteOptions, DEFAULT_BATCH_SIZE,
+    },
+};
+
+/// Generate random events into SQLite, DuckDB and Parquet.
+#[derive(Parser)]
+pub struct Args {
+    /// How many sessions to generate.
+    #[arg(
+        long,
+        env = "GEN_SESSIONS",
+        default_value_t = 100_000,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    sessions: u64,
+
+    /// Seed for the random generator. The same seed produces the same events.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// First day (UTC) of the generated timestamps, e.g. 2023-01-01. Sessions are spread
+    /// evenly until `--end-date` instead of one every 64s on average from the current time.
+    #[arg(long, value_name = "YYYY-MM-DD", requires = "end_date")]
+    start_date: Option<NaiveDate>,
+
+    /// Day (UTC) the generated timestamps end before, requires `--start-date`.
+    #[arg(long, value_name = "YYYY-MM-DD", requires = "start_date")]
+    end_date: Option<NaiveDate>,
+
+    /// JSON file with event kind and page load weights. See `events::Config`.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// File with one word per line used for paths (first 40 words), texts and form values.
+    /// Defaults to 200 common English words.
+    #[arg(long)]
+    words_file: Option<PathBuf>,
+
+    /// File with one user agent per line for page loads. Defaults to a few common browsers.
+    #[arg(long)]
+    user_agents_file: Option<PathBuf>,
+
+    /// Also write events as newline delimited JSON to `events.ndjson`.
+    #[arg(long)]
+    ndjson: bool,
+
+    /// Also write events with the typed payload to an Arrow IPC (Feather) file
+    /// `events-typed.arrow`.
+    #[arg(long)]
+    arrow: bool,
+
+    /// Compress the NDJSON export (appends `.gz`/`.zst`) and Parquet/Arrow files
+    /// (using their native codecs, the file names are kept).
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+
+    /// Schema of the typed payload in DuckDB (Typed), Parquet and Arrow. `scored` adds an
+    /// integer `score` of feedback forms next to the string fields.
+    #[arg(long, value_enum, default_value_t = TypedPayload::Strings)]
+    typed_payload: TypedPayload,
+
+    /// Write a Hive-style partitioned Parquet dataset `events-typed/date=YYYY-MM-DD/` instead
+    /// of `events-typed.parquet`. Read it with `queries --parquet-partitioned`.
+    #[arg(long, value_enum)]
+    parquet_partition_by: Option<PartitionBy>,
+
+    /// Rows per Parquet row group and Arrow record batch.
+    #[arg(
+        long,
+        default_value_t = DEFAULT_BATCH_SIZE,
+        value_parser = clap::value_parser!(u64).range(1..).map(|n| n as usize)
+    )]
+    batch_size: usize,
+
+    /// Number of threads generating sessions. Defaults to the number of CPUs.
+    /// Seeded runs are only reproducible with the same number of threads.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Don't show the progress bar. It is also hidden when stdout isn't a terminal.
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Add events to existing databases instead of failing when the `events` table exists.
+    /// Parquet, Arrow and NDJSON files are rewritten with the new events only.
+    #[arg(long)]
+    append: bool,
+
+    /// How many times a SQLite insert is retried with exponential backoff when the database
+    /// is locked.
+    #[arg(long, default_value_t = 5)]
+    busy_retries: u32,
+
+    /// How to store timestamps. `native` uses each store's timestamp type.
+    #[arg(long, value_enum, default_value_t = TimestampFormat::Native)]
+    timestamps: TimestampFormat,
+
+    /// Generate the sessions without writing anything and print the expected row counts.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Instead of filling the databases, generate ROWS events up front and time inserting
+    /// them into each store one after another. Prints rows/sec per store. Scratch files are
+    /// removed afterwards.
+    #[arg(
+        long,
+        value_name = "ROWS",
+        num_args = 0..=1,
+        default_missing_value = "1000000",
+        conflicts_with_all = ["dry_run", "append"],
+        value_parser = clap::value_parser!(u64).range(1..).map(|n| n as usize)
+    )]
+    bench_inserts: Option<usize>,
+
+    /// Buffer all events in memory and write them in random order instead of roughly by
+    /// timestamp, e.g. to compare compression and row group pruning of unclustered data.
+    #[arg(long, conflicts_with = "dry_run")]
+    shuffle: bool,
+
+    /// Keep the SQLite and DuckDB databases in memory instead of writing them to `data_dir`.
+    /// They are discarded when the generator exits, only `compare-olap all` can query them.
+    /// Parquet, Arrow and NDJSON files are still written.
+    #[arg(long, conflicts_with = "append")]
+    pub in_memory: bool,
+
+    /// Directory for the generated databases and files. Created if it doesn't exist.
+    #[arg(long, env = "COMPARE_OLAP_DATA_DIR", default_value = ".")]
+    pub data_dir: PathBuf,
+}
+
+/// Generate the events and warn that `--in-memory` databases are dropped.
+pub fn run(args: &Args) -> Result<()> {
+    generate(args)?;
+    if args.in_memory && args.bench_inserts.is_none() {
+        tracing::warn!(
+            "In-memory databases are discarded on exit, run without --in-memory to query them \
+             with the queries binary or use `compare-olap all --in-memory`"
+        );
+    }
+    Ok(())
+}
+
+/// Generate the events into every database and file. Returns the open databases, `None`
+/// for `--dry-run` and `--bench-inserts`.
+pub fn generate(args: &Args) -> Result<Option<Databases>> {
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let mut ctx = Ctx::with_config(&config);
+    if let Some(path) = &args.words_file {
+        ctx = ctx.with_words(load_list(path)?);
+    }
+    if let Some(path) = &args.user_agents_file {
+        ctx = ctx.with_user_agents(load_list(path)?);
+    }
+    let ctx = Arc::new(ctx);
+    let seed = args.seed.unwrap_or_else(rand::random);
+
+    let timeline = match (args.start_date, args.end_date) {
+        (Some(start), Some(end)) => {
+            if start >= end {
+                bail!("--start-date {start} must be before --end-date {end}");
+            }
+            let midnight =
+                |date: NaiveDate| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+            Timeline::Range {
+                start: midnight(start),
+                end: midnight(end),
+            }
+        }
+        // Seeded runs start at a fixed point in time so that the output is reproducible.
+        _ => Timeline::Start(match args.seed {
+            Some(_) => Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap(),
+            None => Utc::now(),
+        }),
+    };
+    let max_sessions = args.sessions;
+    tracing::info!("Will insert {max_sessions} sessions");
+
+    if args.dry_run {
+        let session_rxs = spawn_producers(ctx, seed, timeline, max_sessions, args.threads);
+        dry_run(session_rxs);
+        return Ok(None);
+    }
+
+    let opts = common::ExecOptions::from_env()?;
+    let data_dir = common::DataDir::create(&args.data_dir)?;
+    if args.append && args.seed.is_some() {
+        tracing::warn!("Appending with --seed repeats the ids of a previous run with that seed");
+    }
+    let options = WriteOptions {
+        data_dir,
+        timestamps: args.timestamps,
+        append: args.append,
+        busy_retries: args.busy_retries,
+        batch_size: args.batch_size,
+        compress: args.compress,
+        ndjson: args.ndjson,
+        arrow: args.arrow,
+        in_memory: args.in_memory,
+        typed_payload: args.typed_payload,
+        parquet_partition_by: args.parquet_partition_by,
+    };
+    if let Some(rows) = args.bench_inserts {
+        // Every session has at least one event, so `rows` sessions are always enough.
+        let session_rxs = spawn_producers(ctx, seed, timeline, rows as u64, args.threads);
+        let mut events = collect_events(session_rxs, rows);
+        if args.shuffle {
+            events.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        tracing::info!("Generated {} events, inserting", events.len());
+        bench_inserts(&events, &options)?;
+        return Ok(None);
+    }
+    let writers = start_writers(&options, opts)?;
+
+    let session_rxs = spawn_producers(ctx, seed, timeline, max_sessions, args.threads);
+    let mut progress = common::SessionProgress::new(max_sessions, !args.no_progress);
+    let mut order = TimestampOrder::default();
+    if args.shuffle {
+        let mut events = vec![];
+        for_each_session(session_rxs, |session| {
+            progress.inc(session.len());
+            events.extend(session);
+        });
+        progress.finish();
+
+        tracing::info!("Shuffling {} events", events.len());
+        events.shuffle(&mut StdRng::seed_from_u64(seed));
+        for event in events {
+            order.push(event.timestamp);
+            writers.send(event);
+        }
+    } else {
+        for_each_session(session_rxs, |events| {
+            progress.inc(events.len());
+            for event in events {
+                order.push(event.timestamp);
+                writers.send(event);
+            }
+        });
+        progress.finish();
+    }
+    tracing::info!("Done sending events.");
+
+    let databases = writers.finish()?;
+    common::report_sizes(&options.files());
+    order.report();
+
+    tracing::info!("Done.");
+    Ok(Some(databases))
+}
+
+/// Checks whether events were written in timestamp order.
+#[derive(Default)]
+struct TimestampOrder {
+    last: Option<DateTime<Utc>>,
+    events: u64,
+    /// Events with an earlier timestamp than the event written before them.
+    out_of_order: u64,
+}
+
+impl TimestampOrder {
+    fn push(&mut self, timestamp: DateTime<Utc>) {
+        if matches!(self.last, Some(last) if timestamp < last) {
+            self.out_of_order += 1;
+        }
+        self.last = Some(timestamp);
+        self.events += 1;
+    }
+
+    fn report(&self) {
+        if self.out_of_order == 0 {
+            println!("Events are sorted by timestamp");
+        } else {
+            println!(
+                "Events are not sorted by timestamp: {} of {} events are earlier than the one \
+                 before them ({:.1}%)",
+                self.out_of_order,
+                self.events,
+                self.out_of_order as f64 * 100.0 / self.events as f64
+            );
+        }
+    }
+}
+
+/// Length of a session's time slot with [`Timeline::Start`].
+const SESSION_SLOT_MS: i64 = 64_000;
+
+/// Where the timestamps of generated sessions come from.
+#[derive(Clone, Copy)]
+enum Timeline {
+    /// Sessions get 64s slots from the given time on and a random time within their slot.
+    Start(DateTime<Utc>),
+    /// Sessions get evenly sized slots in `[start, end)` and a random time within their slot.
+    Range {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+/// Spawn threads generating `sessions` sessions. Receivers must be drained with
+/// [`for_each_session`] to get the sessions in a reproducible order.
+fn spawn_producers(
+    ctx: Arc<Ctx>,
+    seed: u64,
+    timeline: Timeline,
+    sessions: u64,
+    threads: Option<usize>,
+) -> Vec<Receiver<Vec<Event>>> {
+    // Producer p generates sessions p, p + producers, p + 2 * producers, ... with its own RNG.
+    // Timestamps only depend on the session index, so merging the producers round-robin yields
+    // the sessions in timestamp order and the same seed and thread count produce the same
+    // stream.
+    let producers = threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, sessions as usize) as u64;
+    tracing::info!("Generating with {producers} producer threads");
+
+    let mut session_rxs = vec![];
+    for p in 0..producers {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<Event>>(64);
+        session_rxs.push(rx);
+
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(p));
+
+            for index in (p..sessions).step_by(producers as usize) {
+                let timestamp = match timeline {
+                    Timeline::Start(start) => {
+                        let slot = index as i64 * SESSION_SLOT_MS;
+                        let offset = slot + rng.gen_range(0..SESSION_SLOT_MS);
+                        start + chrono::Duration::milliseconds(offset)
+                    }
+                    Timeline::Range { start, end } => {
+                        let span = (end - start).num_milliseconds() as i128;
+                        let slot_start = span * index as i128 / sessions as i128;
+                        let slot_end = span * (index as i128 + 1) / sessions as i128;
+                        let offset = rng.gen_range(slot_start..slot_end.max(slot_start + 1));
+                        start + chrono::Duration::milliseconds(offset as i64)
+                    }
+                };
+
+                let events = generate_session(&ctx, &mut rng, timestamp);
+                if tx.send(events).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    session_rxs
+}
+
+/// Call `f` with the sessions of every producer in session index, and so timestamp, order.
+fn for_each_session(mut session_rxs: Vec<Receiver<Vec<Event>>>, mut f: impl FnMut(Vec<Event>)) {
+    while !session_rxs.is_empty() {
+        session_rxs.retain(|rx| {
+            let Ok(events) = rx.recv() else {
+                return false;
+            };
+            f(events);
+            true
+        });
+    }
+}
+
+/// Receive sessions in the same order as [`for_each_session`] until there are `rows` events.
+/// Dropping the receivers stops the producers.
+fn collect_events(mut session_rxs: Vec<Receiver<Vec<Event>>>, rows: usize) -> Vec<Event> {
+    let mut events = Vec::with_capacity(rows);
+    let mut next = 0;
+    while events.len() < rows && !session_rxs.is_empty() {
+        match session_rxs[next].recv() {
+            Ok(session) => {
+                events.extend(session);
+                next += 1;
+            }
+            Err(_) => {
+                session_rxs.remove(next);
+            }
+        }
+        if next >= session_rxs.len() {
+            next = 0;
+        }
+    }
+    events.truncate(rows);
+    events
+}
+
+/// Generate sessions without writing them and report what a real run would produce.
+fn dry_run(session_rxs: Vec<Receiver<Vec<Event>>>) {
+    let mut sessions = 0;
+    let mut rows = 0;
+    let mut bytes = 0;
+    let mut per_type = BTreeMap::<&str, u64>::new();
+    let mut payload = String::new();
+    for_each_session(session_rxs, |events| {
+        sessions += 1;
+        for e in events {
+            rows += 1;
+            *per_type.entry(e.event_type()).or_default() += 1;
+            e.payload.write_json(&mut payload);
+            // Raw size of the SQLite row, ignoring indexes and page overhead.
+            bytes += e.id.len()
+                + e.session_id.len()
+                + e.page_id.len()
+                + e.timestamp.to_string().len()
+                + e.event_type().len()
+                + payload.len();
+        }
+    });
+
+    println!("Sessions: {sessions}");
+    println!("Rows: {rows}");
+    for (event_type, count) in per_type {
+        println!("  {event_type}: {count}");
+    }
+    println!("Estimated size: {:.1} MiB", bytes as f64 / (1024.0 * 1024.0));
+}
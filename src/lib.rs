@@ -0,0 +1,10 @@
+//! Event model, data generators and query helpers shared by the binaries.
+
+pub mod benchmarks;
+pub mod common;
+pub mod engines;
+pub mod events;
+pub mod generate;
+pub mod generate_normalized;
+pub mod query;
+pub mod writers;
@@ -0,0 +1,953 @@
+//! Benchmark runner behind `queries` and `compare-olap query`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use clap::{Parser, ValueEnum};
+use datafusion::{
+    arrow::datatypes::DataType,
+    datasource::{file_format::parquet::ParquetFormat, listing::ListingOptions},
+    prelude::{ParquetReadOptions, SessionContext},
+};
+use polars::prelude::LazyFrame;
+use rusqlite::OptionalExtension;
+
+#[cfg(feature = "clickhouse")]
+use crate::common::open_clickhouse;
+#[cfg(feature = "postgres")]
+use crate::common::connect_postgres;
+use crate::{
+    benchmarks::{self, Benchmark, PageLoadsOnDay},
+    common::{
+        capture_output, cold_start, collect_df, compare_results, json_get_udf, open_duck_parquet,
+        print_cold_start, print_versions, query_duck, query_sqlite, register_sqlite,
+        register_sqlite_functions, sqlite_indexes, BenchSummary, ColdStart, ConnectionFactory,
+        DataDir, Engine, ErrorCategory, ExecOptions, OutputFormat, QueryResult, QueryTimedOut,
+        TimestampFormat, DEFAULT_MAX_PRINT_ROWS,
+    },
+    engines::{self, QueryEngine},
+    writers::{Databases, PARQUET_PARTITIONED_DIR},
+};
+
+/// Global allocator of the binaries running queries, logged at startup since Polars and
+/// DataFusion timings depend on it. The binaries set jemalloc with the `jemalloc` feature.
+pub const ALLOCATOR: &str = if cfg!(feature = "jemalloc") {
+    "jemalloc"
+} else {
+    "system"
+};
+
+/// Run analytical queries against every engine.
+#[derive(Parser)]
+pub struct Args {
+    /// Comma separated benchmark names to run, e.g. `top_pages,page_loads_per_day`.
+    /// Runs everything by default.
+    #[arg(long, value_delimiter = ',')]
+    only: Vec<String>,
+
+    /// TOML file with extra queries to run after the built-in ones. Engines without SQL for
+    /// a query are skipped.
+    #[arg(long)]
+    queries_file: Option<PathBuf>,
+
+    /// Comma separated engines to skip, e.g. `--skip-engine sqlite`.
+    #[arg(long, value_delimiter = ',', conflicts_with = "only_engine")]
+    skip_engine: Vec<Engine>,
+
+    /// Comma separated engines to run, e.g. `--only-engine polars`.
+    #[arg(long, value_delimiter = ',')]
+    only_engine: Vec<Engine>,
+
+    /// Abort on the first failing query instead of logging the error and moving on.
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Check that all engines returned the same rows for each query.
+    #[arg(long)]
+    verify: bool,
+
+    /// Exit with an error as soon as DuckDB and DuckDB (Typed) return different rows, or only
+    /// one of them fails, for a query. Both read the same events so any difference is a bug
+    /// in how the payload is stored or queried.
+    #[arg(long, conflicts_with_all = ["explain", "cold_start", "sample_rows"])]
+    assert_duck_parity: bool,
+
+    /// Collect Polars queries with the streaming engine to reduce memory usage.
+    #[arg(long)]
+    polars_streaming: bool,
+
+    /// Report peak RSS of each query next to its timing (Linux only).
+    #[arg(long)]
+    measure_memory: bool,
+
+    /// Don't truncate long values in table output.
+    #[arg(long)]
+    full_width: bool,
+
+    /// Print results as uncolored ASCII tables, same as `COMPARE_OLAP_FORMAT=plain`.
+    #[arg(long)]
+    plain: bool,
+
+    /// Directory with the generated databases and files.
+    #[arg(long, env = "COMPARE_OLAP_DATA_DIR", default_value = ".")]
+    pub data_dir: PathBuf,
+
+    /// Only query the first N rows of `events` for quick smoke tests. Results won't match a
+    /// full run. Not supported by ClickHouse.
+    #[arg(long)]
+    sample_rows: Option<usize>,
+
+    /// Abandon a query that runs longer than SECS and report it as timed out instead of failing
+    /// the run. Not supported by ClickHouse.
+    #[arg(long, value_name = "SECS")]
+    query_timeout: Option<u64>,
+
+    /// Print Polars and DataFusion query plans instead of running the queries. Other engines
+    /// are skipped.
+    #[arg(long)]
+    explain: bool,
+
+    /// Print DuckDB's `EXPLAIN ANALYZE` plan with operator timings after each DuckDB query.
+    /// The plan comes from an extra run, so reported timings are unaffected.
+    #[arg(long)]
+    duck_explain: bool,
+
+    /// Word the `text_search` benchmark looks for in chat messages.
+    #[arg(long, default_value = benchmarks::DEFAULT_SEARCH_TERM)]
+    search_term: String,
+
+    /// Path the `form_funnel` benchmark starts from, e.g. `/before`. Generated data only has
+    /// paths from the first 40 words of `--words-file`.
+    #[arg(long, default_value = benchmarks::DEFAULT_FUNNEL_PATH)]
+    funnel_path: String,
+
+    /// Print at most N rows of each result, 0 prints everything. Results are still collected
+    /// in full. CSV and JSON output is never cut.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_PRINT_ROWS)]
+    max_print_rows: usize,
+
+    /// Print only the column names and timing of each query, no result rows. Same as
+    /// `COMPARE_OLAP_QUIET=1`. Bench output still records row counts.
+    #[arg(long)]
+    quiet: bool,
+
+    /// Time opening each embedded engine plus the first query of the first selected benchmark
+    /// against the following warm runs, instead of running the benchmarks.
+    #[arg(long, conflicts_with_all = ["explain", "sample_rows"])]
+    cold_start: bool,
+
+    /// Run all engines of a query at the same time, each on its own thread, and report the
+    /// wall time. Output is buffered per engine and printed once all of them are done.
+    #[arg(long, conflicts_with_all = ["explain", "cold_start", "measure_memory"])]
+    parallel_engines: bool,
+
+    /// Where DataFusion reads the `events` table from.
+    #[arg(long, value_enum, default_value_t = DataFusionSource::Parquet)]
+    datafusion_source: DataFusionSource,
+
+    /// Read the day-partitioned `events-typed/` dataset from `gen_data --parquet-partition-by
+    /// day` instead of `events-typed.parquet`. DataFusion gets a `date` partition column and
+    /// prunes partitions on it, Polars and DuckDB (Parquet) scan all files.
+    #[arg(long)]
+    parquet_partitioned: bool,
+
+    /// Timestamp format the data was generated with. Benchmarks that truncate timestamps to
+    /// days or hours are skipped with `epoch-millis`, naming one with `--only` is an error.
+    #[arg(long, value_enum, default_value_t = TimestampFormat::Native)]
+    timestamps: TimestampFormat,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum DataFusionSource {
+    /// `events-typed.parquet` with the typed payload struct.
+    Parquet,
+    /// SQLite `events` table loaded into memory, payload is a JSON string.
+    Sqlite,
+}
+
+impl Args {
+    fn enabled(&self, engine: Engine) -> bool {
+        if !self.only_engine.is_empty() {
+            return self.only_engine.contains(&engine);
+        }
+        !self.skip_engine.contains(&engine)
+    }
+
+    /// Parquet file, or a glob over the partitioned dataset with `--parquet-partitioned`.
+    fn parquet_path(&self, data_dir: &DataDir) -> String {
+        if self.parquet_partitioned {
+            format!("{}/*/*.parquet", data_dir.file(PARQUET_PARTITIONED_DIR))
+        } else {
+            data_dir.file("events-typed.parquet")
+        }
+    }
+
+    /// Name of the Parquet file or dataset directory in the data directory.
+    fn parquet_file(&self) -> &'static str {
+        if self.parquet_partitioned {
+            PARQUET_PARTITIONED_DIR
+        } else {
+            "events-typed.parquet"
+        }
+    }
+
+    /// Files of enabled engines that don't exist. The normalized SQLite database is optional
+    /// and checked when it's opened. `in_memory` skips the databases passed to [`run`].
+    fn missing_files(&self, data_dir: &DataDir, in_memory: bool) -> Vec<String> {
+        let mut files = vec![];
+        if self.enabled(Engine::Sqlite) && !in_memory {
+            files.push("eventsqlite.db");
+        }
+        if self.enabled(Engine::Duck) && !in_memory {
+            files.push("eventsduck.db");
+        }
+        if self.enabled(Engine::DuckTyped) && !in_memory {
+            files.push("eventsduck-typed.db");
+        }
+        if [Engine::DuckParquet, Engine::Polars, Engine::PolarsSql]
+            .iter()
+            .any(|engine| self.enabled(*engine))
+        {
+            files.push(self.parquet_file());
+        }
+        if self.enabled(Engine::DataFusion) {
+            files.push(match self.datafusion_source {
+                DataFusionSource::Parquet => self.parquet_file(),
+                DataFusionSource::Sqlite => "eventsqlite.db",
+            });
+        }
+        #[cfg(feature = "clickhouse")]
+        if self.enabled(Engine::ClickHouse) {
+            files.push("eventsclickhouse");
+        }
+        #[cfg(feature = "msgpack")]
+        if self.enabled(Engine::SqliteMsgpack) && !in_memory {
+            files.push("eventsqlite-msgpack.db");
+        }
+        files.sort_unstable();
+        files.dedup();
+
+        files
+            .into_iter()
+            .map(|name| data_dir.file(name))
+            .filter(|path| !Path::new(path).exists())
+            .collect()
+    }
+
+    /// `--parallel-engines` opens the database read-only, see [`ConnectionFactory`].
+    fn open_sqlite(&self, path: String) -> Result<rusqlite::Connection> {
+        if self.parallel_engines {
+            return ConnectionFactory::new(path).sqlite();
+        }
+        Ok(rusqlite::Connection::open(path)?)
+    }
+
+    /// Same as [`Args::open_sqlite`] for DuckDB.
+    fn open_duck(&self, path: String) -> Result<duckdb::Connection> {
+        if self.parallel_engines {
+            return ConnectionFactory::new(path).duck();
+        }
+        Ok(duckdb::Connection::open(path)?)
+    }
+
+    /// Log a failed query and carry on unless `--fail-fast` is set.
+    /// Timed out queries never abort the run, failures are classified and recorded.
+    fn check(
+        &self,
+        result: Result<QueryResult>,
+        name: &str,
+        engine: Engine,
+        opts: &ExecOptions,
+    ) -> Result<Option<QueryResult>> {
+        let timeout = result.as_ref().err().and_then(|e| e.downcast_ref::<QueryTimedOut>());
+        if let Some(timeout) = timeout {
+            println!("{} {timeout}", engine.label());
+            println!();
+            opts.summary.add_timeout(engine.label());
+            return Ok(None);
+        }
+        let err = match result {
+            Ok(result) => return Ok(Some(result)),
+            Err(err) => err,
+        };
+        let category = ErrorCategory::classify(&err);
+        let err = err.context(format!(
+            "Query {name} failed on {} ({} error)",
+            engine.label(),
+            category.name()
+        ));
+        opts.summary.add_error(engine.label(), category);
+        opts.recorder.record_error(engine.label(), name, category, &format!("{err:#}"))?;
+        if self.fail_fast {
+            return Err(err);
+        }
+        tracing::error!("{err:#}");
+        Ok(None)
+    }
+}
+
+/// Run the benchmarks. SQLite and DuckDB `events` are read from `databases` when given,
+/// e.g. the in-memory databases of `compare-olap all`, otherwise from the data directory.
+pub async fn run(args: &Args, databases: Option<Databases>) -> Result<()> {
+    tracing::info!("Using {ALLOCATOR} allocator");
+    let mut opts = ExecOptions {
+        polars_streaming: args.polars_streaming,
+        measure_memory: args.measure_memory,
+        timeout: args.query_timeout.map(Duration::from_secs),
+        max_print_rows: (args.max_print_rows > 0).then_some(args.max_print_rows),
+        duck_explain: args.duck_explain,
+        ..ExecOptions::from_env()?
+    };
+    if args.full_width {
+        opts.column_width = None;
+    }
+    if args.plain {
+        opts.format = OutputFormat::Plain;
+    }
+    if args.quiet {
+        opts.quiet = true;
+    }
+
+    let data_dir = DataDir::create(&args.data_dir)?;
+
+    let mut benches = benchmarks::all_with_params(&args.search_term, &args.funnel_path)?;
+    if args.parquet_partitioned {
+        if let Some(day) = last_partition(&data_dir.file(PARQUET_PARTITIONED_DIR))? {
+            let partitioned = matches!(args.datafusion_source, DataFusionSource::Parquet);
+            benches.push(Box::new(PageLoadsOnDay::new(day, partitioned)));
+        }
+    }
+    if let Some(path) = &args.queries_file {
+        benches.extend(benchmarks::load_queries_file(path)?);
+    }
+    if !args.only.is_empty() {
+        let unknown: Vec<_> = args
+            .only
+            .iter()
+            .filter(|name| !benches.iter().any(|b| b.name() == name.as_str()))
+            .collect();
+        if !unknown.is_empty() {
+            let available: Vec<_> = benches.iter().map(|b| b.name()).collect();
+            bail!(
+                "Unknown benchmark(s): {:?}\nAvailable: {}",
+                unknown,
+                available.join(", ")
+            );
+        }
+        benches.retain(|b| args.only.iter().any(|name| name == b.name()));
+    }
+    if args.timestamps == TimestampFormat::EpochMillis {
+        let native: Vec<_> = benches
+            .iter()
+            .filter(|b| b.needs_native_timestamps())
+            .map(|b| b.name())
+            .collect();
+        if !native.is_empty() {
+            if !args.only.is_empty() {
+                bail!(
+                    "{} can't run against --timestamps epoch-millis data, they read calendar \
+                     fields of native timestamps",
+                    native.join(", ")
+                );
+            }
+            tracing::warn!("Skipping {}, they need native timestamps", native.join(", "));
+            benches.retain(|b| !b.needs_native_timestamps());
+        }
+    }
+
+    // Skipped engines are never opened so their files don't need to exist.
+    let missing = args.missing_files(&data_dir, databases.is_some());
+    if !missing.is_empty() {
+        let files: Vec<_> = missing.iter().map(|file| format!("{file} not found")).collect();
+        bail!(
+            "{}\nRun `cargo run --release --bin gen_data` first or skip the engines reading them \
+             with --skip-engine",
+            files.join("\n")
+        );
+    }
+    let duck_engines = [Engine::Duck, Engine::DuckTyped];
+    if args.assert_duck_parity && !duck_engines.iter().all(|engine| args.enabled(*engine)) {
+        bail!("--assert-duck-parity needs both the duck and duck-typed engines");
+    }
+    if args.cold_start {
+        if databases.is_some() {
+            bail!("--cold-start opens the databases from disk, it can't time in-memory databases");
+        }
+        print_versions(&opts)?;
+        return run_cold_start(args, &data_dir, benches[0].as_ref(), &opts).await;
+    }
+    #[cfg(feature = "msgpack")]
+    let mut sqlite_msgpack = None;
+    let (sqlite, duck, duck_typed) = match databases {
+        Some(databases) => {
+            #[cfg(feature = "msgpack")]
+            {
+                sqlite_msgpack = Some(databases.sqlite_msgpack);
+            }
+            (
+                Some(databases.sqlite),
+                Some(databases.duck),
+                Some(databases.duck_typed),
+            )
+        }
+        None => (None, None, None),
+    };
+    let sqlite_conn = args
+        .enabled(Engine::Sqlite)
+        .then(|| match sqlite {
+            Some(conn) => Ok(conn),
+            None => args.open_sqlite(data_dir.file("eventsqlite.db")),
+        })
+        .transpose()?;
+    let sqlite_normalized_conn = args
+        .enabled(Engine::SqliteNormalized)
+        .then(|| open_existing_sqlite(&data_dir.file("normalqlite.db")))
+        .transpose()?
+        .flatten();
+    if let Some(conn) = &sqlite_conn {
+        check_timestamps(conn, args.timestamps)?;
+    }
+    for conn in [&sqlite_conn, &sqlite_normalized_conn].into_iter().flatten() {
+        register_sqlite_functions(conn)?;
+    }
+    #[cfg(feature = "msgpack")]
+    let sqlite_msgpack_conn = args
+        .enabled(Engine::SqliteMsgpack)
+        .then(|| match sqlite_msgpack {
+            Some(conn) => Ok(conn),
+            None => args.open_sqlite(data_dir.file("eventsqlite-msgpack.db")),
+        })
+        .transpose()?;
+    #[cfg(feature = "msgpack")]
+    if let Some(conn) = &sqlite_msgpack_conn {
+        register_sqlite_functions(conn)?;
+    }
+    #[cfg(not(feature = "msgpack"))]
+    if !args.only_engine.is_empty() && args.enabled(Engine::SqliteMsgpack) {
+        tracing::warn!("MessagePack is not compiled in, rebuild with `--features msgpack`");
+    }
+    let duck_conn = args
+        .enabled(Engine::Duck)
+        .then(|| match duck {
+            Some(conn) => Ok(conn),
+            None => args.open_duck(data_dir.file("eventsduck.db")),
+        })
+        .transpose()?;
+    let duck_typed_conn = args
+        .enabled(Engine::DuckTyped)
+        .then(|| match duck_typed {
+            Some(conn) => Ok(conn),
+            None => args.open_duck(data_dir.file("eventsduck-typed.db")),
+        })
+        .transpose()?;
+    let parquet_path = args.parquet_path(&data_dir);
+    let duck_parquet_conn = args
+        .enabled(Engine::DuckParquet)
+        .then(|| open_duck_parquet(&parquet_path))
+        .transpose()?;
+    let mut pdf = (args.enabled(Engine::Polars) || args.enabled(Engine::PolarsSql))
+        .then(|| LazyFrame::scan_parquet(&parquet_path, Default::default()))
+        .transpose()?;
+
+    // Temporary views shadow the `events` table so that queries don't need to change.
+    if let Some(n) = args.sample_rows {
+        tracing::warn!("Sampling {n} rows of events, results won't match a full run");
+        for conn in [&sqlite_conn, &sqlite_normalized_conn].into_iter().flatten() {
+            conn.execute_batch(&sample_view_sql("main.events", n))?;
+        }
+        #[cfg(feature = "msgpack")]
+        if let Some(conn) = &sqlite_msgpack_conn {
+            conn.execute_batch(&sample_view_sql("main.events", n))?;
+        }
+        for conn in [&duck_conn, &duck_typed_conn, &duck_parquet_conn].into_iter().flatten() {
+            conn.execute_batch(&sample_view_sql("main.events", n))?;
+        }
+        pdf = pdf.map(|pdf| pdf.limit(n as u32));
+    }
+    // Indexes decide whether SQLite scans, so show which ones the databases were built with.
+    for (engine, conn) in [
+        (Engine::Sqlite, &sqlite_conn),
+        (Engine::SqliteNormalized, &sqlite_normalized_conn),
+    ] {
+        if let Some(conn) = conn {
+            let indexes = sqlite_indexes(conn, "events")?;
+            let indexes = if indexes.is_empty() {
+                "none".to_string()
+            } else {
+                indexes.join(", ")
+            };
+            println!("{} indexes: {indexes}", engine.label());
+        }
+    }
+    if let Some(pdf) = &pdf {
+        println!("Polar schema: {:?}", pdf.schema());
+    }
+    let dfctx = if args.enabled(Engine::DataFusion) {
+        let dfctx = open_datafusion(args, &data_dir).await?;
+        if let Some(n) = args.sample_rows {
+            let sample = dfctx.table("events").await?.limit(0, Some(n))?;
+            dfctx.deregister_table("events")?;
+            dfctx.register_table("events", sample.into_view())?;
+        }
+        Some(dfctx)
+    } else {
+        None
+    };
+
+    #[cfg(feature = "clickhouse")]
+    let ch_session = args
+        .enabled(Engine::ClickHouse)
+        .then(|| open_clickhouse(&data_dir.file("eventsclickhouse")))
+        .transpose()?;
+    #[cfg(feature = "clickhouse")]
+    if ch_session.is_some() && args.sample_rows.is_some() {
+        tracing::warn!("ClickHouse doesn't support --sample-rows, querying all rows");
+    }
+    #[cfg(feature = "clickhouse")]
+    if ch_session.is_some() && args.query_timeout.is_some() {
+        tracing::warn!("ClickHouse doesn't support --query-timeout, queries run to completion");
+    }
+    #[cfg(not(feature = "clickhouse"))]
+    if !args.only_engine.is_empty() && args.enabled(Engine::ClickHouse) {
+        tracing::warn!("ClickHouse is not compiled in, rebuild with `--features clickhouse`");
+    }
+
+    #[cfg(feature = "postgres")]
+    let pg_client = if args.enabled(Engine::Postgres) {
+        connect_postgres().await?
+    } else {
+        None
+    };
+    #[cfg(feature = "postgres")]
+    if let (Some(client), Some(n)) = (&pg_client, args.sample_rows) {
+        client.batch_execute(&sample_view_sql("public.events", n)).await?;
+    }
+    #[cfg(not(feature = "postgres"))]
+    if !args.only_engine.is_empty() && args.enabled(Engine::Postgres) {
+        tracing::warn!("Postgres is not compiled in, rebuild with `--features postgres`");
+    }
+
+    let mut query_engines: Vec<Box<dyn QueryEngine>> = vec![];
+    if let Some(conn) = sqlite_conn {
+        query_engines.push(Box::new(engines::Sqlite(conn)));
+    }
+    if let Some(conn) = sqlite_normalized_conn {
+        query_engines.push(Box::new(engines::SqliteNormalized(conn)));
+    }
+    #[cfg(feature = "msgpack")]
+    if let Some(conn) = sqlite_msgpack_conn {
+        query_engines.push(Box::new(engines::SqliteMsgpack(conn)));
+    }
+    if let Some(conn) = duck_conn {
+        query_engines.push(Box::new(engines::Duck(conn)));
+    }
+    if let Some(conn) = duck_typed_conn {
+        query_engines.push(Box::new(engines::DuckTyped(conn)));
+    }
+    if let Some(conn) = duck_parquet_conn {
+        query_engines.push(Box::new(engines::DuckParquet(conn)));
+    }
+    if let Some(pdf) = &pdf {
+        if args.enabled(Engine::Polars) {
+            query_engines.push(Box::new(engines::Polars(pdf.clone())));
+        }
+        if args.enabled(Engine::PolarsSql) {
+            query_engines.push(Box::new(engines::PolarsSql(pdf.clone())));
+        }
+    }
+    if let Some(ctx) = dfctx {
+        query_engines.push(Box::new(engines::DataFusion(ctx)));
+    }
+    #[cfg(feature = "clickhouse")]
+    if let Some(session) = ch_session {
+        query_engines.push(Box::new(engines::ClickHouse(session)));
+    }
+    #[cfg(feature = "postgres")]
+    if let Some(client) = pg_client {
+        query_engines.push(Box::new(engines::Postgres(client)));
+    }
+
+    print_versions(&opts)?;
+    tracing::info!("Starting to execute queries");
+
+    for bench in benches {
+        let name = bench.name();
+
+        println!();
+        println!("========================================================================");
+        println!("{}", bench.title());
+        for note in bench.notes() {
+            println!("{note}");
+        }
+        println!("========================================================================");
+        println!();
+
+        if args.explain {
+            for engine in &query_engines {
+                if let Some(plan) = engine.explain(bench.as_ref(), &opts) {
+                    println!("{} plan:", engine.name());
+                    println!("{}", plan?);
+                }
+            }
+            continue;
+        }
+
+        if let Some(dir) = &opts.plan_output {
+            for engine in &query_engines {
+                write_plan(dir, bench.as_ref(), engine.as_ref(), &opts)?;
+            }
+        }
+
+        let mut results = vec![];
+        if args.parallel_engines {
+            for (engine, res) in run_parallel(&mut query_engines, bench.as_ref(), &opts) {
+                if let Some(res) = args.check(res, name, engine, &opts)? {
+                    results.push((engine.label(), res));
+                }
+            }
+        } else {
+            for engine in &query_engines {
+                let Some(res) = engine.run(bench.as_ref(), &opts) else {
+                    continue;
+                };
+                if let Some(res) = args.check(res, name, engine.engine(), &opts)? {
+                    results.push((engine.name(), res));
+                }
+            }
+        }
+
+        if let Some((baseline, candidate)) = bench.speedup() {
+            print_speedup(&results, &opts.summary, baseline, candidate);
+        }
+        // Every query with both Polars paths shows what going through SQL costs.
+        print_speedup(&results, &opts.summary, Engine::Polars, Engine::PolarsSql);
+
+        if args.verify {
+            compare_results(&results);
+            check_invariants(bench.as_ref(), &results);
+        }
+
+        if args.assert_duck_parity {
+            assert_duck_parity(bench.as_ref(), &results)?;
+        }
+    }
+
+    if !args.explain {
+        println!();
+        opts.summary.print();
+    }
+
+    tracing::info!("Done.");
+    Ok(())
+}
+
+/// Fail when the JSON and typed DuckDB databases disagree on `bench`, printing the differing
+/// rows. Benchmarks without SQL for both of them are skipped.
+fn assert_duck_parity(bench: &dyn Benchmark, results: &[(&str, QueryResult)]) -> Result<()> {
+    if bench.duck_sql().is_none() || bench.duck_typed_sql().is_none() {
+        return Ok(());
+    }
+
+    let find = |engine: Engine| {
+        results
+            .iter()
+            .find(|(label, _)| *label == engine.label())
+            .cloned()
+    };
+    match (find(Engine::Duck), find(Engine::DuckTyped)) {
+        (Some(json), Some(typed)) => {
+            if !compare_results(&[json, typed]) {
+                bail!("DuckDB and DuckDB (Typed) results differ for {}", bench.name());
+            }
+        }
+        // Both errors are already reported, they don't diverge.
+        (None, None) => {}
+        (Some(_), None) => bail!("{} failed only on DuckDB (Typed)", bench.name()),
+        (None, Some(_)) => bail!("{} failed only on DuckDB", bench.name()),
+    }
+    Ok(())
+}
+
+/// Fail when `events.timestamp` in SQLite wasn't stored in `expected` format. The date
+/// benchmarks would return wrong results instead of failing otherwise.
+fn check_timestamps(conn: &rusqlite::Connection, expected: TimestampFormat) -> Result<()> {
+    let kind: Option<String> = conn
+        .query_row("SELECT typeof(timestamp) FROM events LIMIT 1", [], |row| row.get(0))
+        .optional()?;
+    match (kind.as_deref(), expected) {
+        (Some("integer"), TimestampFormat::Native) => {
+            bail!("eventsqlite.db stores epoch-millis timestamps, pass --timestamps epoch-millis")
+        }
+        (Some("text"), TimestampFormat::EpochMillis) => {
+            bail!("eventsqlite.db stores native timestamps, drop --timestamps epoch-millis")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Open the normalized database only if it was generated. It's optional so a missing file
+/// is skipped with a warning instead of creating an empty database.
+fn open_existing_sqlite(path: &str) -> Result<Option<rusqlite::Connection>> {
+    if !Path::new(path).exists() {
+        tracing::warn!("{path} not found, run gen_data_normalized to include it");
+        return Ok(None);
+    }
+    Ok(Some(rusqlite::Connection::open(path)?))
+}
+
+/// Latest day of the `date=YYYY-MM-DD` partitions in `dir`, `None` if there are none.
+fn last_partition(dir: &str) -> Result<Option<NaiveDate>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(None);
+    };
+    let mut last = None;
+    for entry in entries {
+        let name = entry?.file_name();
+        let day = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("date="))
+            .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok());
+        last = last.max(day);
+    }
+    Ok(last)
+}
+
+/// DataFusion context with `events` registered from `--datafusion-source`.
+async fn open_datafusion(args: &Args, data_dir: &DataDir) -> Result<SessionContext> {
+    let dfctx = SessionContext::new();
+    dfctx.register_udf(json_get_udf());
+    match args.datafusion_source {
+        DataFusionSource::Parquet if args.parquet_partitioned => {
+            let options = ListingOptions::new(Arc::new(ParquetFormat::default()))
+                .with_file_extension(".parquet")
+                .with_table_partition_cols(vec![("date".to_owned(), DataType::Utf8)]);
+            let dir = format!("{}/", data_dir.file(PARQUET_PARTITIONED_DIR));
+            dfctx.register_listing_table("events", &dir, options, None, None).await?
+        }
+        DataFusionSource::Parquet => {
+            let parquet_path = data_dir.file("events-typed.parquet");
+            dfctx
+                .register_parquet("events", &parquet_path, ParquetReadOptions::default())
+                .await?
+        }
+        DataFusionSource::Sqlite => {
+            register_sqlite(&dfctx, &data_dir.file("eventsqlite.db"), "events")?
+        }
+    }
+    Ok(dfctx)
+}
+
+/// Time opening each embedded engine together with the first query of `bench` against warm
+/// runs on the same connection. Postgres and ClickHouse are left out.
+async fn run_cold_start(
+    args: &Args,
+    data_dir: &DataDir,
+    bench: &dyn Benchmark,
+    opts: &ExecOptions,
+) -> Result<()> {
+    println!("Cold start of {}", bench.title());
+    println!();
+    let mut rows = vec![];
+
+    if let Some(sql) = bench.sqlite_sql().filter(|_| args.enabled(Engine::Sqlite)) {
+        let row = cold_start(
+            Engine::Sqlite,
+            opts,
+            || {
+                let conn = rusqlite::Connection::open(data_dir.file("eventsqlite.db"))?;
+                register_sqlite_functions(&conn)?;
+                Ok(conn)
+            },
+            |conn| query_sqlite(conn, sql),
+        )?;
+        rows.push(row);
+    }
+    let duck_files = [
+        (Engine::Duck, bench.duck_sql(), "eventsduck.db"),
+        (Engine::DuckTyped, bench.duck_typed_sql(), "eventsduck-typed.db"),
+    ];
+    for (engine, sql, file) in duck_files {
+        if let Some(sql) = sql.filter(|_| args.enabled(engine)) {
+            let row = cold_start(
+                engine,
+                opts,
+                || Ok(duckdb::Connection::open(data_dir.file(file))?),
+                |conn| query_duck(conn, sql, bench.duck_columns()),
+            )?;
+            rows.push(row);
+        }
+    }
+    let parquet_path = args.parquet_path(data_dir);
+    if let Some(sql) = bench.duck_typed_sql().filter(|_| args.enabled(Engine::DuckParquet)) {
+        let row = cold_start(
+            Engine::DuckParquet,
+            opts,
+            || open_duck_parquet(&parquet_path),
+            |conn| query_duck(conn, sql, bench.duck_columns()),
+        )?;
+        rows.push(row);
+    }
+    if args.enabled(Engine::Polars) {
+        let events = LazyFrame::scan_parquet(&parquet_path, Default::default())?;
+        if let Some(lf) = bench.polars(events) {
+            let row = cold_start(Engine::Polars, opts, || Ok(lf), |lf| Ok(lf.clone().collect()?))?;
+            rows.push(row);
+        }
+    }
+    // DataFusion is async so it's timed here instead of with `cold_start`.
+    if let Some(sql) = bench.datafusion_sql().filter(|_| args.enabled(Engine::DataFusion)) {
+        let now = Instant::now();
+        let ctx = open_datafusion(args, data_dir).await?;
+        collect_df(&ctx, sql).await?;
+        let cold = now.elapsed();
+        for _ in 0..opts.warmup {
+            collect_df(&ctx, sql).await?;
+        }
+        let mut warm = vec![];
+        for _ in 0..opts.iterations.max(1) {
+            let now = Instant::now();
+            collect_df(&ctx, sql).await?;
+            warm.push(now.elapsed());
+        }
+        warm.sort();
+        rows.push(ColdStart {
+            engine: Engine::DataFusion,
+            cold,
+            warm: warm[warm.len() / 2],
+        });
+    }
+
+    print_cold_start(&mut rows);
+    Ok(())
+}
+
+/// Run `bench` on every engine at the same time, each on its own thread. Output of each engine
+/// is buffered and printed in engine order once all of them are done, followed by the wall time
+/// compared to running the engines one after another.
+fn run_parallel(
+    engines: &mut [Box<dyn QueryEngine>],
+    bench: &dyn Benchmark,
+    opts: &ExecOptions,
+) -> Vec<(Engine, Result<QueryResult>)> {
+    // DataFusion and Postgres block on the tokio runtime from their threads.
+    let runtime = tokio::runtime::Handle::current();
+    let started = Instant::now();
+    let runs: Vec<_> = thread::scope(|s| {
+        let handles: Vec<_> = engines
+            .iter_mut()
+            .map(|engine| {
+                let runtime = runtime.clone();
+                s.spawn(move || {
+                    let _guard = runtime.enter();
+                    let now = Instant::now();
+                    let (res, output) = capture_output(|| engine.run(bench, opts));
+                    (engine.engine(), res, output, now.elapsed())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Engine thread panicked"))
+            .collect()
+    });
+    let wall = started.elapsed();
+
+    let mut sequential = Duration::ZERO;
+    let mut results = vec![];
+    for (engine, res, output, elapsed) in runs {
+        let Some(res) = res else {
+            continue;
+        };
+        print!("{output}");
+        sequential += elapsed;
+        results.push((engine, res));
+    }
+    println!(
+        "{} engines in parallel took {}ms wall time, {}ms one after another ({:.2}x speedup)",
+        results.len(),
+        wall.as_millis(),
+        sequential.as_millis(),
+        sequential.as_secs_f64() / wall.as_secs_f64().max(f64::EPSILON),
+    );
+    println!();
+    results
+}
+
+/// Save the engine's plan of `bench` to `<query_name>.<engine>.plan.txt` in `dir`. A plan
+/// that can't be produced is logged, the query itself reports the error.
+fn write_plan(
+    dir: &Path,
+    bench: &dyn Benchmark,
+    engine: &dyn QueryEngine,
+    opts: &ExecOptions,
+) -> Result<()> {
+    let plan = match engine.plan(bench, opts) {
+        Some(Ok(plan)) => plan,
+        Some(Err(err)) => {
+            tracing::warn!("No {} plan for {}: {err:#}", engine.name(), bench.name());
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+    let engine_name = engine
+        .engine()
+        .to_possible_value()
+        .expect("Engines are never skipped");
+    let path = dir.join(format!("{}.{}.plan.txt", bench.name(), engine_name.get_name()));
+    fs::write(&path, plan).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Temporary view named `events` with the first `n` rows of `table`.
+fn sample_view_sql(table: &str, n: usize) -> String {
+    format!("CREATE TEMP VIEW events AS SELECT * FROM {table} LIMIT {n}")
+}
+
+/// Print results that don't match the benchmark's expected row count or invariant.
+fn check_invariants(bench: &dyn Benchmark, results: &[(&str, QueryResult)]) {
+    for (engine, result) in results {
+        if let Some(expected) = bench.expected_rows() {
+            if result.rows.len() != expected {
+                println!(
+                    "INVALID: {engine} returned {} rows, expected {expected}",
+                    result.rows.len()
+                );
+            }
+        }
+        if let Some(problem) = bench.check(result) {
+            println!("INVALID: {engine} {problem}");
+        }
+    }
+}
+
+/// Print how much faster `candidate` was than `baseline` if both succeeded.
+fn print_speedup(
+    results: &[(&str, QueryResult)],
+    summary: &BenchSummary,
+    baseline: Engine,
+    candidate: Engine,
+) {
+    let ran = |engine: Engine| results.iter().any(|(label, _)| *label == engine.label());
+    if !ran(baseline) || !ran(candidate) {
+        return;
+    }
+    let base = summary.last(baseline.label());
+    let cand = summary.last(candidate.label());
+    let (Some(base), Some(cand)) = (base, cand) else {
+        return;
+    };
+    println!(
+        "{} vs {}: {:.2}x speedup ({}ms vs {}ms)",
+        candidate.label(),
+        baseline.label(),
+        base.as_secs_f64() / cand.as_secs_f64().max(f64::EPSILON),
+        cand.as_millis(),
+        base.as_millis(),
+    );
+}
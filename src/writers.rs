@@ -0,0 +1,1411 @@
+//! Writer threads that store events in every database and file format.
+//!
+//! Used by the generators and the CSV importer so that all of them produce the same tables.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::mpsc::{Receiver, SyncSender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use polars::{
+    io::{ipc::BatchedWriter as IpcBatchedWriter, parquet::BatchedWriter},
+    prelude::{
+        DataFrame, DataType, IntoSeries, IpcCompression, IpcWriter, ListChunked, NamedFrom,
+        ParquetCompression, ParquetWriter, Series, StructChunked, TimeUnit,
+    },
+};
+use serde_json::json;
+
+use crate::{
+    common::{self, format_timestamp_for, DataDir, Engine, ExecOptions, TimestampFormat},
+    events::Event,
+};
+
+/// Where and how [`start_writers`] stores events.
+pub struct WriteOptions {
+    pub data_dir: DataDir,
+    pub timestamps: TimestampFormat,
+    /// Add to existing `events` tables instead of exiting when they exist.
+    pub append: bool,
+    /// How many times a locked SQLite insert is retried.
+    pub busy_retries: u32,
+    /// Rows per Parquet row group and Arrow record batch.
+    pub batch_size: usize,
+    pub compress: Compression,
+    /// Also write `events.ndjson`.
+    pub ndjson: bool,
+    /// Also write `events-typed.arrow`.
+    pub arrow: bool,
+    /// Schema of the typed payload in DuckDB (Typed), Parquet and Arrow.
+    pub typed_payload: TypedPayload,
+    /// Write a partitioned Parquet dataset to [`PARQUET_PARTITIONED_DIR`] instead of
+    /// `events-typed.parquet`.
+    pub parquet_partition_by: Option<PartitionBy>,
+    /// Open SQLite and DuckDB databases with `:memory:` instead of files in `data_dir`. They
+    /// only live as long as the [`Databases`] returned by [`Writers::finish`], files are still
+    /// written to `data_dir`.
+    pub in_memory: bool,
+}
+
+impl WriteOptions {
+    /// Databases and the Parquet file written by [`start_writers`], in-memory databases are
+    /// left out.
+    pub fn files(&self) -> Vec<String> {
+        let mut files = vec![];
+        if !self.in_memory {
+            files.push(self.data_dir.file("eventsqlite.db"));
+            #[cfg(feature = "msgpack")]
+            files.push(self.data_dir.file("eventsqlite-msgpack.db"));
+            files.push(self.data_dir.file("eventsduck.db"));
+            files.push(self.data_dir.file("eventsduck-typed.db"));
+        }
+        // Partitioned files are counted by the Parquet writer instead.
+        if self.parquet_partition_by.is_none() {
+            files.push(self.data_dir.file("events-typed.parquet"));
+        }
+        files
+    }
+}
+
+/// Connections to the databases filled by the writers.
+pub struct Databases {
+    pub sqlite: rusqlite::Connection,
+    pub duck: duckdb::Connection,
+    pub duck_typed: duckdb::Connection,
+    #[cfg(feature = "msgpack")]
+    pub sqlite_msgpack: rusqlite::Connection,
+}
+
+/// Running writer threads, see [`start_writers`].
+pub struct Writers {
+    senders: Vec<SyncSender<Event>>,
+    handles: Vec<JoinHandle<()>>,
+    sqlite_handle: JoinHandle<rusqlite::Connection>,
+    duck_handle: JoinHandle<duckdb::Connection>,
+    duck_typed_handle: JoinHandle<duckdb::Connection>,
+    #[cfg(feature = "msgpack")]
+    sqlite_msgpack_handle: JoinHandle<rusqlite::Connection>,
+    null_rates_opts: ExecOptions,
+}
+
+impl Writers {
+    /// Send the event to every writer.
+    pub fn send(&self, event: Event) {
+        for tx in &self.senders {
+            tx.send(event.clone()).unwrap();
+        }
+    }
+
+    /// Wait for the writers to store all sent events and print the NULL rates of the
+    /// databases.
+    pub fn finish(self) -> Result<Databases> {
+        drop(self.senders);
+        for handle in self.handles {
+            handle.join().unwrap();
+        }
+        let databases = Databases {
+            sqlite: self.sqlite_handle.join().unwrap(),
+            duck: self.duck_handle.join().unwrap(),
+            duck_typed: self.duck_typed_handle.join().unwrap(),
+            #[cfg(feature = "msgpack")]
+            sqlite_msgpack: self.sqlite_msgpack_handle.join().unwrap(),
+        };
+
+        print_null_rates(&databases, &self.null_rates_opts)?;
+        #[cfg(feature = "msgpack")]
+        print_msgpack_sizes(&databases)?;
+        Ok(databases)
+    }
+}
+
+/// Create the `events` tables and spawn a writer thread per database and file. Fails when a
+/// database already has events unless `append` is set.
+pub fn start_writers(options: &WriteOptions, opts: ExecOptions) -> Result<Writers> {
+    let data_dir = &options.data_dir;
+    let timestamps = options.timestamps;
+    let typed_payload = options.typed_payload;
+
+    // Prepare databases
+    let db_path = |name: &str| {
+        if options.in_memory {
+            MEMORY_PATH.to_string()
+        } else {
+            data_dir.file(name)
+        }
+    };
+    let sqlite_path = db_path("eventsqlite.db");
+    let sqlite_conn = rusqlite::Connection::open(&sqlite_path).unwrap();
+    sqlite_conn
+        .pragma_update(None, "journal_mode", "WAL")
+        .unwrap();
+    sqlite_conn
+        .pragma_update(None, "busy_timeout", SQLITE_BUSY_TIMEOUT_MS)
+        .unwrap();
+    let duck_path = db_path("eventsduck.db");
+    let duck_conn = duckdb::Connection::open(&duck_path).unwrap();
+    let duck_typed_path = db_path("eventsduck-typed.db");
+    let duck_typed_conn = duckdb::Connection::open(&duck_typed_path).unwrap();
+
+    // Check every database before creating anything so that a failed check leaves no
+    // half-initialized files behind.
+    let create_sqlite = should_create(
+        common::sqlite_table_exists(&sqlite_conn, "events")?,
+        options.append,
+        &sqlite_path,
+    )?;
+    let create_duck = should_create(
+        common::duck_table_exists(&duck_conn, "events")?,
+        options.append,
+        &duck_path,
+    )?;
+    let create_duck_typed = should_create(
+        common::duck_table_exists(&duck_typed_conn, "events")?,
+        options.append,
+        &duck_typed_path,
+    )?;
+    #[cfg(feature = "msgpack")]
+    let (sqlite_msgpack_conn, create_sqlite_msgpack) = {
+        let path = db_path("eventsqlite-msgpack.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let exists = common::sqlite_table_exists(&conn, "events")?;
+        (conn, should_create(exists, options.append, &path)?)
+    };
+
+    if create_sqlite {
+        create_sqlite_table(&sqlite_conn, timestamps, "TEXT").unwrap();
+    }
+    if create_duck {
+        create_duck_table(&duck_conn, Engine::Duck, timestamps, "JSON").unwrap();
+    }
+    if create_duck_typed {
+        create_duck_table(
+            &duck_typed_conn,
+            Engine::DuckTyped,
+            timestamps,
+            typed_payload.duck_type(),
+        )
+        .unwrap();
+    }
+
+    let (sqlite_tx, sqlite_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+    let (duck_tx, duck_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+    let (duck_typed_tx, duck_typed_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+    let (parquet_tx, parquet_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+
+    let null_rates_opts = opts.clone();
+    let sqlite_opts = opts.clone();
+    let busy_retries = options.busy_retries;
+    let sqlite_handle = thread::spawn(move || {
+        tracing::info!("SQLite worker running");
+
+        let started = Instant::now();
+        let rows = insert_sqlite(&sqlite_conn, sqlite_rx, timestamps, busy_retries).unwrap();
+
+        let elapsed = started.elapsed();
+        tracing::info!(
+            "SQLite rows written: {rows} in {elapsed:?} ({:.0} rows/sec)",
+            rows as f64 / elapsed.as_secs_f64()
+        );
+
+        tracing::info!("Count SQLite");
+        common::exec_sqlite(
+            &sqlite_conn,
+            "count",
+            "SELECT count(*) FROM events",
+            &sqlite_opts,
+        )
+        .unwrap();
+        sqlite_conn
+    });
+
+    let duck_opts = opts.clone();
+    let duck_handle = thread::spawn(move || {
+        tracing::info!("DuckDB worker running");
+
+        let started = Instant::now();
+        let rows = append_duck(&duck_conn, duck_rx, timestamps).unwrap();
+
+        let elapsed = started.elapsed();
+        tracing::info!(
+            "DuckDB rows written: {rows} in {elapsed:?} ({:.0} rows/sec)",
+            rows as f64 / elapsed.as_secs_f64()
+        );
+
+        tracing::info!("Count DuckDB");
+        common::exec_duck(
+            &duck_conn,
+            "count",
+            "SELECT count(*) FROM events",
+            None,
+            &duck_opts,
+        )
+        .unwrap();
+        duck_conn
+    });
+
+    let duck_typed_staging = data_dir.file("events-typed-staging.ndjson");
+    let duck_typed_handle = thread::spawn(move || {
+        tracing::info!("DuckDB-typed worker running");
+
+        let started = Instant::now();
+        let rows = load_duck_typed(
+            &duck_typed_conn,
+            duck_typed_rx,
+            &duck_typed_staging,
+            timestamps,
+            typed_payload,
+        )
+        .unwrap();
+
+        let elapsed = started.elapsed();
+        tracing::info!(
+            "DuckDB Typed rows written: {rows} in {elapsed:?} ({:.0} rows/sec)",
+            rows as f64 / elapsed.as_secs_f64()
+        );
+
+        tracing::info!("Count DuckDB Typed");
+        common::exec_duck_typed(
+            &duck_typed_conn,
+            "count",
+            "SELECT count(*) FROM events",
+            None,
+            &opts,
+        )
+        .unwrap();
+        duck_typed_conn
+    });
+
+    let mut senders = vec![sqlite_tx, duck_tx, duck_typed_tx, parquet_tx];
+    let mut handles = vec![];
+
+    let batch_size = options.batch_size;
+    let compress = options.compress;
+    let parquet_path = data_dir.file("events-typed.parquet");
+    let parquet_dir = data_dir.file(PARQUET_PARTITIONED_DIR);
+    let partition_by = options.parquet_partition_by;
+    handles.push(thread::spawn(move || {
+        tracing::info!("Parquet worker running");
+
+        if partition_by == Some(PartitionBy::Day) {
+            let (files, rows) = write_parquet_by_day(parquet_rx, &parquet_dir, |path| {
+                FrameSink::create(
+                    path,
+                    SinkFormat::Parquet,
+                    batch_size,
+                    compress,
+                    timestamps,
+                    typed_payload,
+                )
+            })
+            .unwrap();
+            tracing::info!("Parquet rows written: {rows} into {files} files in {parquet_dir}");
+            return;
+        }
+
+        let mut sink = FrameSink::create(
+            &parquet_path,
+            SinkFormat::Parquet,
+            batch_size,
+            compress,
+            timestamps,
+            typed_payload,
+        )
+        .unwrap();
+        while let Ok(e) = parquet_rx.recv() {
+            sink.push(e).unwrap();
+        }
+
+        let rows = sink.finish().unwrap();
+        tracing::info!("Parquet rows written: {rows}");
+    }));
+
+    if options.arrow {
+        let (arrow_tx, arrow_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+        senders.push(arrow_tx);
+
+        let arrow_path = data_dir.file("events-typed.arrow");
+        handles.push(thread::spawn(move || {
+            tracing::info!("Arrow IPC worker running");
+
+            let mut sink = FrameSink::create(
+                &arrow_path,
+                SinkFormat::Ipc,
+                batch_size,
+                compress,
+                timestamps,
+                typed_payload,
+            )
+            .unwrap();
+            while let Ok(e) = arrow_rx.recv() {
+                sink.push(e).unwrap();
+            }
+
+            let rows = sink.finish().unwrap();
+            tracing::info!("Arrow IPC rows written: {rows}");
+        }));
+    }
+
+    if options.ndjson {
+        let (ndjson_tx, ndjson_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+        senders.push(ndjson_tx);
+
+        let ndjson_path = data_dir.file(&format!("events.ndjson{}", compress.extension()));
+        handles.push(thread::spawn(move || {
+            tracing::info!("NDJSON worker running");
+
+            let file = File::create(ndjson_path).unwrap();
+            let mut out = BufWriter::new(compress.writer(file).unwrap());
+            let mut rows = 0;
+            while let Ok(e) = ndjson_rx.recv() {
+                let timestamp = format_timestamp_for(Engine::Duck, timestamps, e.timestamp);
+                let line = json!({
+                    "id": e.id,
+                    "session_id": e.session_id,
+                    "page_id": e.page_id,
+                    "timestamp": timestamp.to_json(),
+                    "event_type": e.event_type(),
+                    "payload": e.payload.to_json(),
+                });
+                writeln!(out, "{line}").unwrap();
+
+                rows += 1;
+                // Flush periodically so that a crash doesn't lose everything.
+                if rows % 10_000 == 0 {
+                    out.flush().unwrap();
+                }
+            }
+
+            let out = out.into_inner().map_err(|err| err.into_error()).unwrap();
+            out.finish().unwrap();
+            tracing::info!("NDJSON rows written: {rows}");
+        }));
+    }
+
+    #[cfg(feature = "msgpack")]
+    let sqlite_msgpack_handle = {
+        let (msgpack_tx, msgpack_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+        senders.push(msgpack_tx);
+
+        let conn = sqlite_msgpack_conn;
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        conn.pragma_update(None, "busy_timeout", SQLITE_BUSY_TIMEOUT_MS).unwrap();
+        if create_sqlite_msgpack {
+            create_sqlite_table(&conn, timestamps, "BLOB").unwrap();
+        }
+
+        let msgpack_opts = opts.clone();
+        thread::spawn(move || {
+            tracing::info!("SQLite MessagePack worker running");
+
+            let started = Instant::now();
+            let rows = insert_sqlite_msgpack(&conn, msgpack_rx, timestamps, busy_retries).unwrap();
+
+            let elapsed = started.elapsed();
+            tracing::info!(
+                "SQLite MessagePack rows written: {rows} in {elapsed:?} ({:.0} rows/sec)",
+                rows as f64 / elapsed.as_secs_f64()
+            );
+
+            common::exec_sqlite_msgpack(
+                &conn,
+                "count",
+                "SELECT count(*) FROM events",
+                &msgpack_opts,
+            )
+            .unwrap();
+            conn
+        })
+    };
+
+    #[cfg(feature = "clickhouse")]
+    {
+        let (ch_tx, ch_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+        senders.push(ch_tx);
+
+        let ch_opts = opts.clone();
+        let ch_path = data_dir.file("eventsclickhouse");
+        handles.push(thread::spawn(move || {
+            tracing::info!("ClickHouse worker running");
+
+            let session = common::open_clickhouse(&ch_path).unwrap();
+            session
+                .execute("CREATE DATABASE IF NOT EXISTS olap", None)
+                .unwrap();
+            session
+                .execute(
+                    &format!(
+                        r#"
+CREATE TABLE IF NOT EXISTS olap.events (
+    id String,
+    session_id String,
+    page_id String,
+    timestamp {},
+    event_type String,
+    payload String
+) ENGINE = MergeTree ORDER BY timestamp
+"#,
+                        timestamps.column_type(Engine::ClickHouse)
+                    ),
+                    None,
+                )
+                .unwrap();
+
+            let insert = |rows: &mut Vec<String>| {
+                let query = format!(
+                    "INSERT INTO olap.events FORMAT JSONEachRow\n{}",
+                    rows.join("\n")
+                );
+                session.execute(&query, None).unwrap();
+                rows.clear();
+            };
+
+            let mut rows = Vec::with_capacity(10_000);
+            let mut payload = String::new();
+            while let Ok(e) = ch_rx.recv() {
+                e.payload.write_json(&mut payload);
+                let timestamp = format_timestamp_for(Engine::ClickHouse, timestamps, e.timestamp);
+                let row = json!({
+                    "id": e.id,
+                    "session_id": e.session_id,
+                    "page_id": e.page_id,
+                    "timestamp": timestamp.to_json(),
+                    "event_type": e.event_type(),
+                    "payload": payload,
+                });
+                rows.push(row.to_string());
+
+                if rows.len() == 10_000 {
+                    insert(&mut rows);
+                }
+            }
+            if !rows.is_empty() {
+                insert(&mut rows);
+            }
+
+            common::exec_clickhouse(
+                &session,
+                "count",
+                "SELECT count(*) FROM olap.events",
+                &ch_opts,
+            )
+            .unwrap();
+        }));
+    }
+
+    #[cfg(feature = "postgres")]
+    {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        if let Some(client) = rt.block_on(common::connect_postgres()).unwrap() {
+            let (pg_tx, pg_rx) = std::sync::mpsc::sync_channel::<Event>(1);
+            senders.push(pg_tx);
+
+            let pg_opts = opts.clone();
+            let append = options.append;
+            handles.push(thread::spawn(move || {
+                tracing::info!("Postgres worker running");
+
+                rt.block_on(async {
+                    use tokio_postgres::types::ToSql;
+
+                    client
+                        .batch_execute(&format!(
+                            r#"
+{}
+CREATE TABLE IF NOT EXISTS events (
+  id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  page_id TEXT NOT NULL,
+  timestamp {} NOT NULL,
+  event_type TEXT NOT NULL,
+  payload JSONB
+);
+"#,
+                            if append { "" } else { "DROP TABLE IF EXISTS events;" },
+                            timestamps.column_type(Engine::Postgres)
+                        ))
+                        .await
+                        .unwrap();
+
+                    let insert = client
+                        .prepare(
+                            r#"
+INSERT INTO events (id, session_id, page_id, timestamp, event_type, payload)
+  VALUES ($1, $2, $3, $4, $5, $6)"#,
+                        )
+                        .await
+                        .unwrap();
+
+                    // Commit in batches, a transaction per row is very slow.
+                    let mut rows = 0;
+                    client.batch_execute("BEGIN").await.unwrap();
+                    while let Ok(e) = pg_rx.recv() {
+                        // TIMESTAMP columns only accept naive values.
+                        let timestamp: Box<dyn ToSql + Sync> =
+                            match format_timestamp_for(Engine::Postgres, timestamps, e.timestamp) {
+                                common::TimestampValue::EpochMillis(ms) => Box::new(ms),
+                                _ => Box::new(e.timestamp.naive_utc()),
+                            };
+                        client
+                            .execute(
+                                &insert,
+                                &[
+                                    &e.id,
+                                    &e.session_id,
+                                    &e.page_id,
+                                    &*timestamp,
+                                    &e.event_type(),
+                                    &e.payload.to_json(),
+                                ],
+                            )
+                            .await
+                            .unwrap();
+
+                        rows += 1;
+                        if rows % 10_000 == 0 {
+                            client.batch_execute("COMMIT; BEGIN").await.unwrap();
+                        }
+                    }
+                    client.batch_execute("COMMIT").await.unwrap();
+
+                    common::exec_postgres(
+                        &client,
+                        "count",
+                        "SELECT count(*) FROM events",
+                        &pg_opts,
+                    )
+                    .await
+                    .unwrap();
+                });
+            }));
+        }
+    }
+
+    Ok(Writers {
+        senders,
+        handles,
+        sqlite_handle,
+        duck_handle,
+        duck_typed_handle,
+        #[cfg(feature = "msgpack")]
+        sqlite_msgpack_handle,
+        null_rates_opts,
+    })
+}
+
+const SQLITE_NULL_RATES_SQL: &str = r#"
+SELECT COUNT(*) AS events,
+       AVG(payload IS NULL) AS payload,
+       AVG(payload->>'$.path' IS NULL) AS path,
+       AVG(payload->>'$.user_agent' IS NULL) AS user_agent,
+       AVG(payload->>'$.text' IS NULL) AS text,
+       AVG(payload->>'$.form_type' IS NULL) AS form_type,
+       AVG(payload->'$.fields' IS NULL) AS fields
+  FROM events
+"#;
+
+const DUCK_NULL_RATES_SQL: &str = r#"
+SELECT COUNT(*) AS events,
+       AVG(CAST(payload IS NULL AS INTEGER)) AS payload,
+       AVG(CAST(payload->>'$.path' IS NULL AS INTEGER)) AS path,
+       AVG(CAST(payload->>'$.user_agent' IS NULL AS INTEGER)) AS user_agent,
+       AVG(CAST(payload->>'$.text' IS NULL AS INTEGER)) AS text,
+       AVG(CAST(payload->>'$.form_type' IS NULL AS INTEGER)) AS form_type,
+       AVG(CAST(payload->'$.fields' IS NULL AS INTEGER)) AS fields
+  FROM events
+"#;
+
+const DUCK_TYPED_NULL_RATES_SQL: &str = r#"
+SELECT COUNT(*) AS events,
+       AVG(CAST(payload IS NULL AS INTEGER)) AS payload,
+       AVG(CAST(payload.path IS NULL AS INTEGER)) AS path,
+       AVG(CAST(payload.user_agent IS NULL AS INTEGER)) AS user_agent,
+       AVG(CAST(payload.text IS NULL AS INTEGER)) AS text,
+       AVG(CAST(payload.form_type IS NULL AS INTEGER)) AS form_type,
+       AVG(CAST(payload.fields IS NULL AS INTEGER)) AS fields
+  FROM events
+"#;
+
+/// Print the fraction of NULL payload values (and payload fields) in each database so that
+/// query results can be related to the generated distribution.
+fn print_null_rates(databases: &Databases, opts: &common::ExecOptions) -> Result<()> {
+    tracing::info!("Null rates");
+    common::exec_sqlite(&databases.sqlite, "null_rates", SQLITE_NULL_RATES_SQL, opts)?;
+    common::exec_duck(&databases.duck, "null_rates", DUCK_NULL_RATES_SQL, None, opts)?;
+    common::exec_duck_typed(
+        &databases.duck_typed,
+        "null_rates",
+        DUCK_TYPED_NULL_RATES_SQL,
+        None,
+        opts,
+    )?;
+    Ok(())
+}
+
+/// Payload bytes and on-disk size of the SQLite database with JSON text against the one with
+/// MessagePack BLOBs.
+#[cfg(feature = "msgpack")]
+fn print_msgpack_sizes(databases: &Databases) -> Result<()> {
+    let sizes = |conn: &rusqlite::Connection| {
+        conn.query_row(
+            r#"
+SELECT (SELECT COALESCE(SUM(length(CAST(payload AS BLOB))), 0) FROM events),
+       page_count * page_size
+  FROM pragma_page_count(), pragma_page_size()
+"#,
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+        )
+    };
+    let (json_payload, json_db) = sizes(&databases.sqlite)?;
+    let (msgpack_payload, msgpack_db) = sizes(&databases.sqlite_msgpack)?;
+
+    let mib = |bytes: i64| bytes as f64 / (1024.0 * 1024.0);
+    let change = |json: i64, msgpack: i64| (msgpack as f64 / json.max(1) as f64 - 1.0) * 100.0;
+    println!(
+        "Payload size: JSON {:.1} MiB, MessagePack {:.1} MiB ({:+.0}%)",
+        mib(json_payload),
+        mib(msgpack_payload),
+        change(json_payload, msgpack_payload)
+    );
+    println!(
+        "SQLite size: JSON {:.1} MiB, MessagePack {:.1} MiB ({:+.0}%)",
+        mib(json_db),
+        mib(msgpack_db),
+        change(json_db, msgpack_db)
+    );
+    Ok(())
+}
+
+/// Whether the `events` table at `path` has to be created. Fails when it already exists
+/// unless `--append` is set.
+fn should_create(exists: bool, append: bool, path: &str) -> Result<bool> {
+    if exists && !append {
+        bail!("{path} already has an events table. Remove it or pass --append to add to it.");
+    }
+    Ok(!exists)
+}
+
+/// Run `f` again with exponential backoff while SQLite reports the database as busy or locked.
+fn with_busy_retry<T>(
+    retries: u32,
+    mut f: impl FnMut() -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    let mut delay = Duration::from_millis(10);
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if attempt < retries
+                    && matches!(
+                        err.code,
+                        rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                    ) =>
+            {
+                attempt += 1;
+                tracing::warn!("SQLite is busy, retry {attempt}/{retries} in {delay:?}");
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            res => return res,
+        }
+    }
+}
+
+const SQLITE_INSERT_SQL: &str = r#"
+INSERT INTO events (id, session_id, page_id, timestamp, event_type, payload)
+  VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#;
+
+/// Create the SQLite `events` table with the given payload column type.
+fn create_sqlite_table(
+    conn: &rusqlite::Connection,
+    timestamps: TimestampFormat,
+    payload_type: &str,
+) -> Result<()> {
+    conn.execute_batch(&format!(
+        r#"
+CREATE TABLE events (
+  id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  page_id TEXT NOT NULL,
+  timestamp {} NOT NULL,
+  event_type TEXT NOT NULL,
+  payload {payload_type}
+);
+"#,
+        timestamps.column_type(Engine::Sqlite)
+    ))?;
+    Ok(())
+}
+
+/// Create the DuckDB `events` table of `engine` with the given payload column type.
+fn create_duck_table(
+    conn: &duckdb::Connection,
+    engine: Engine,
+    timestamps: TimestampFormat,
+    payload_type: &str,
+) -> Result<()> {
+    conn.execute_batch(&format!(
+        r#"
+CREATE TABLE events (
+  id VARCHAR NOT NULL,
+  session_id VARCHAR NOT NULL,
+  page_id VARCHAR NOT NULL,
+  timestamp {} NOT NULL,
+  event_type VARCHAR NOT NULL,
+  payload {payload_type}
+);
+"#,
+        timestamps.column_type(engine)
+    ))?;
+    Ok(())
+}
+
+/// Insert events with a JSON payload into SQLite. Returns the number of rows inserted.
+fn insert_sqlite(
+    conn: &rusqlite::Connection,
+    events: impl IntoIterator<Item = Event>,
+    timestamps: TimestampFormat,
+    busy_retries: u32,
+) -> Result<u64> {
+    let mut stmt = conn.prepare(SQLITE_INSERT_SQL)?;
+
+    // Commit in batches, a transaction per row is very slow.
+    let mut rows = 0;
+    let commit = || with_busy_retry(busy_retries, || conn.execute_batch("COMMIT"));
+    let mut payload = String::new();
+    conn.execute_batch("BEGIN")?;
+    for e in events {
+        e.payload.write_json(&mut payload);
+        let timestamp = format_timestamp_for(Engine::Sqlite, timestamps, e.timestamp);
+        with_busy_retry(busy_retries, || {
+            stmt.execute(rusqlite::params![
+                e.id,
+                e.session_id,
+                e.page_id,
+                timestamp,
+                e.event_type(),
+                payload,
+            ])
+        })?;
+
+        rows += 1;
+        if rows % SQLITE_COMMIT_ROWS == 0 {
+            commit()?;
+            conn.execute_batch("BEGIN")?;
+        }
+    }
+    commit()?;
+    Ok(rows)
+}
+
+/// Insert events with a MessagePack payload into SQLite. Returns the number of rows inserted.
+#[cfg(feature = "msgpack")]
+fn insert_sqlite_msgpack(
+    conn: &rusqlite::Connection,
+    events: impl IntoIterator<Item = Event>,
+    timestamps: TimestampFormat,
+    busy_retries: u32,
+) -> Result<u64> {
+    let mut stmt = conn.prepare(SQLITE_INSERT_SQL)?;
+
+    let mut rows = 0;
+    let commit = || with_busy_retry(busy_retries, || conn.execute_batch("COMMIT"));
+    conn.execute_batch("BEGIN")?;
+    for e in events {
+        let payload = rmp_serde::to_vec(&e.payload)?;
+        let timestamp = format_timestamp_for(Engine::Sqlite, timestamps, e.timestamp);
+        with_busy_retry(busy_retries, || {
+            stmt.execute(rusqlite::params![
+                e.id,
+                e.session_id,
+                e.page_id,
+                timestamp,
+                e.event_type(),
+                payload,
+            ])
+        })?;
+
+        rows += 1;
+        if rows % SQLITE_COMMIT_ROWS == 0 {
+            commit()?;
+            conn.execute_batch("BEGIN")?;
+        }
+    }
+    commit()?;
+    Ok(rows)
+}
+
+/// Append events with a JSON payload to DuckDB. Returns the number of rows appended.
+fn append_duck(
+    conn: &duckdb::Connection,
+    events: impl IntoIterator<Item = Event>,
+    timestamps: TimestampFormat,
+) -> Result<u64> {
+    let mut rows = 0;
+    let mut appender = conn.appender("events")?;
+    let mut payload = String::new();
+    for e in events {
+        e.payload.write_json(&mut payload);
+        appender.append_row(duckdb::params![
+            e.id,
+            e.session_id,
+            e.page_id,
+            format_timestamp_for(Engine::Duck, timestamps, e.timestamp),
+            e.event_type(),
+            payload,
+        ])?;
+
+        rows += 1;
+        if rows % DUCK_APPEND_ROWS == 0 {
+            appender.flush();
+        }
+    }
+    appender.flush();
+    Ok(rows)
+}
+
+/// Load events with a typed payload into DuckDB through an NDJSON file at `staging`, which
+/// is removed afterwards. Returns the number of rows loaded.
+fn load_duck_typed(
+    conn: &duckdb::Connection,
+    events: impl IntoIterator<Item = Event>,
+    staging: &str,
+    timestamps: TimestampFormat,
+    typed_payload: TypedPayload,
+) -> Result<u64> {
+    // The appender can't write nested values. Write events as NDJSON and load the file
+    // with DuckDB's JSON reader. The explicit columns match the `events` table so the
+    // payload is parsed into the same struct instead of being inferred.
+    let mut rows = 0;
+    let mut out = BufWriter::new(File::create(staging)?);
+    for e in events {
+        let timestamp = format_timestamp_for(Engine::DuckTyped, timestamps, e.timestamp);
+        let mut payload = json!(e.payload);
+        if let (TypedPayload::Scored, Some(score)) = (typed_payload, e.payload.score()) {
+            payload["score"] = json!(score);
+        }
+        let line = json!({
+            "id": e.id,
+            "session_id": e.session_id,
+            "page_id": e.page_id,
+            "timestamp": timestamp.to_json(),
+            "event_type": e.event_type(),
+            "payload": payload,
+        });
+        writeln!(out, "{line}")?;
+        rows += 1;
+    }
+    out.flush()?;
+    drop(out);
+
+    conn.execute_batch(&format!(
+        r#"
+INSERT INTO events
+  SELECT id, session_id, page_id, timestamp, event_type, payload
+    FROM read_json(
+      '{}',
+      format = 'newline_delimited',
+      columns = {{
+        id: 'VARCHAR',
+        session_id: 'VARCHAR',
+        page_id: 'VARCHAR',
+        timestamp: '{}',
+        event_type: 'VARCHAR',
+        payload: '{}'
+      }}
+    );
+"#,
+        staging.replace('\'', "''"),
+        timestamps.column_type(Engine::DuckTyped),
+        typed_payload.duck_type()
+    ))?;
+    fs::remove_file(staging)?;
+    Ok(rows)
+}
+
+/// Insert `events` into each embedded database and the Parquet file, one store at a time so
+/// that they don't compete for CPU and disk, and print the throughput of each. The stores
+/// are scratch files in the data directory that are removed afterwards (databases are
+/// `:memory:` with `in_memory`). Events are cloned before the clock starts.
+pub fn bench_inserts(events: &[Event], options: &WriteOptions) -> Result<()> {
+    let data_dir = &options.data_dir;
+    let timestamps = options.timestamps;
+    let typed_payload = options.typed_payload;
+    let db_path = |name: &str| {
+        if options.in_memory {
+            MEMORY_PATH.to_string()
+        } else {
+            data_dir.file(&format!("insert-bench-{name}"))
+        }
+    };
+    let mut results = vec![];
+
+    let path = db_path("sqlite.db");
+    remove_scratch(&path)?;
+    let conn = rusqlite::Connection::open(&path)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    create_sqlite_table(&conn, timestamps, "TEXT")?;
+    let batch = events.to_vec();
+    let started = Instant::now();
+    insert_sqlite(&conn, batch, timestamps, 0)?;
+    results.push((Engine::Sqlite.label(), started.elapsed()));
+    drop(conn);
+    remove_scratch(&path)?;
+
+    #[cfg(feature = "msgpack")]
+    {
+        let path = db_path("sqlite-msgpack.db");
+        remove_scratch(&path)?;
+        let conn = rusqlite::Connection::open(&path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        create_sqlite_table(&conn, timestamps, "BLOB")?;
+        let batch = events.to_vec();
+        let started = Instant::now();
+        insert_sqlite_msgpack(&conn, batch, timestamps, 0)?;
+        results.push((Engine::SqliteMsgpack.label(), started.elapsed()));
+        drop(conn);
+        remove_scratch(&path)?;
+    }
+
+    let path = db_path("duck.db");
+    remove_scratch(&path)?;
+    let conn = duckdb::Connection::open(&path)?;
+    create_duck_table(&conn, Engine::Duck, timestamps, "JSON")?;
+    let batch = events.to_vec();
+    let started = Instant::now();
+    append_duck(&conn, batch, timestamps)?;
+    results.push((Engine::Duck.label(), started.elapsed()));
+    drop(conn);
+    remove_scratch(&path)?;
+
+    let path = db_path("duck-typed.db");
+    remove_scratch(&path)?;
+    let conn = duckdb::Connection::open(&path)?;
+    create_duck_table(&conn, Engine::DuckTyped, timestamps, typed_payload.duck_type())?;
+    let staging = data_dir.file("insert-bench-staging.ndjson");
+    let batch = events.to_vec();
+    let started = Instant::now();
+    load_duck_typed(&conn, batch, &staging, timestamps, typed_payload)?;
+    results.push((Engine::DuckTyped.label(), started.elapsed()));
+    drop(conn);
+    remove_scratch(&path)?;
+
+    let path = data_dir.file("insert-bench.parquet");
+    let mut sink = FrameSink::create(
+        &path,
+        SinkFormat::Parquet,
+        options.batch_size,
+        options.compress,
+        timestamps,
+        typed_payload,
+    )?;
+    let batch = events.to_vec();
+    let started = Instant::now();
+    for e in batch {
+        sink.push(e)?;
+    }
+    sink.finish()?;
+    results.push(("Parquet", started.elapsed()));
+    remove_scratch(&path)?;
+
+    println!("Inserted {} rows into each store", events.len());
+    println!("{:<20} {:>10} {:>12}", "Store", "secs", "rows/sec");
+    for (label, elapsed) in results {
+        println!(
+            "{:<20} {:>10.2} {:>12.0}",
+            label,
+            elapsed.as_secs_f64(),
+            events.len() as f64 / elapsed.as_secs_f64()
+        );
+    }
+    Ok(())
+}
+
+/// Remove a scratch database or file of [`bench_inserts`] together with its WAL files.
+fn remove_scratch(path: &str) -> Result<()> {
+    if path == MEMORY_PATH {
+        return Ok(());
+    }
+    for path in [
+        path.to_string(),
+        format!("{path}-wal"),
+        format!("{path}-shm"),
+        format!("{path}.wal"),
+    ] {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Directory of the partitioned Parquet dataset, see [`PartitionBy`].
+pub const PARQUET_PARTITIONED_DIR: &str = "events-typed";
+
+/// How the Parquet output is split into files.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PartitionBy {
+    /// Hive-style `date=YYYY-MM-DD/part.parquet` directories by the UTC day of the event.
+    Day,
+}
+
+/// Write events into a `date=YYYY-MM-DD/part.parquet` file per day under `dir`, replacing
+/// a previous dataset. Returns the number of files and rows written.
+fn write_parquet_by_day(
+    rx: Receiver<Event>,
+    dir: &str,
+    create: impl Fn(&str) -> Result<FrameSink>,
+) -> Result<(usize, usize)> {
+    if Path::new(dir).exists() {
+        fs::remove_dir_all(dir)?;
+    }
+
+    let mut sinks = BTreeMap::new();
+    while let Ok(e) = rx.recv() {
+        let sink = match sinks.entry(e.timestamp.date_naive()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let partition = Path::new(dir).join(format!("date={}", entry.key()));
+                fs::create_dir_all(&partition)?;
+                entry.insert(create(&partition.join("part.parquet").to_string_lossy())?)
+            }
+        };
+        sink.push(e)?;
+    }
+
+    let files = sinks.len();
+    let mut rows = 0;
+    for sink in sinks.into_values() {
+        rows += sink.finish()?;
+    }
+    Ok((files, rows))
+}
+
+/// Schema of the typed `payload` struct.
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TypedPayload {
+    /// Form fields only as `fields STRUCT(name VARCHAR, value VARCHAR)[]`, the score is a
+    /// string that queries have to cast.
+    #[default]
+    Strings,
+    /// Also a top-level `score INTEGER` of feedback forms next to the string fields.
+    Scored,
+}
+
+impl TypedPayload {
+    /// DuckDB type of the `payload` column.
+    fn duck_type(self) -> &'static str {
+        match self {
+            TypedPayload::Strings => {
+                "STRUCT(
+    path VARCHAR,
+    user_agent VARCHAR,
+    text VARCHAR,
+    form_type VARCHAR,
+    fields STRUCT(name VARCHAR, value VARCHAR)[]
+  )"
+            }
+            TypedPayload::Scored => {
+                "STRUCT(
+    path VARCHAR,
+    user_agent VARCHAR,
+    text VARCHAR,
+    form_type VARCHAR,
+    fields STRUCT(name VARCHAR, value VARCHAR)[],
+    score INTEGER
+  )"
+            }
+        }
+    }
+}
+
+/// Compression of the NDJSON export and the Parquet and Arrow files.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// NDJSON writer that has to be finished to write the compression footer.
+enum CompressedWriter {
+    Plain(File),
+    Gzip(flate2::write::GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    fn writer(self, file: File) -> Result<CompressedWriter> {
+        Ok(match self {
+            Compression::None => CompressedWriter::Plain(file),
+            Compression::Gzip => CompressedWriter::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// `None` keeps the Parquet writer's default codec.
+    fn parquet(self) -> Option<ParquetCompression> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some(ParquetCompression::Gzip(None)),
+            Compression::Zstd => Some(ParquetCompression::Zstd(None)),
+        }
+    }
+
+    /// Arrow IPC supports only LZ4 and ZSTD, gzip leaves the file uncompressed.
+    fn ipc(self) -> Option<IpcCompression> {
+        match self {
+            Compression::Zstd => Some(IpcCompression::ZSTD),
+            Compression::None | Compression::Gzip => None,
+        }
+    }
+}
+
+impl CompressedWriter {
+    fn finish(self) -> Result<()> {
+        match self {
+            CompressedWriter::Plain(mut file) => file.flush()?,
+            CompressedWriter::Gzip(encoder) => {
+                encoder.finish()?;
+            }
+            CompressedWriter::Zstd(encoder) => {
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(w) => w.write(buf),
+            CompressedWriter::Gzip(w) => w.write(buf),
+            CompressedWriter::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWriter::Plain(w) => w.flush(),
+            CompressedWriter::Gzip(w) => w.flush(),
+            CompressedWriter::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+/// How many events are buffered before they are written as a Parquet row group
+/// or an Arrow record batch.
+pub const DEFAULT_BATCH_SIZE: usize = 100_000;
+
+/// Database path that SQLite and DuckDB open as an in-memory database.
+const MEMORY_PATH: &str = ":memory:";
+
+/// How many rows the SQLite worker inserts per transaction.
+const SQLITE_COMMIT_ROWS: u64 = 10_000;
+
+/// How long SQLite itself waits for a lock before returning `SQLITE_BUSY`.
+const SQLITE_BUSY_TIMEOUT_MS: u32 = 5_000;
+
+/// How many rows the DuckDB workers append before flushing a chunk.
+const DUCK_APPEND_ROWS: u64 = 100_000;
+
+#[derive(Clone, Copy)]
+enum SinkFormat {
+    Parquet,
+    /// Arrow IPC (Feather v2).
+    Ipc,
+}
+
+enum SinkWriter {
+    Parquet(BatchedWriter<File>),
+    Ipc(IpcBatchedWriter<File>),
+}
+
+/// Writes events into a Parquet or Arrow IPC file in batches.
+///
+/// The schema matches what DuckDB produces when exporting the typed events table
+/// (`COPY events TO 'events-typed.parquet'`).
+struct FrameSink {
+    format: SinkFormat,
+    compress: Compression,
+    timestamps: TimestampFormat,
+    typed_payload: TypedPayload,
+    file: Option<File>,
+    writer: Option<SinkWriter>,
+    batch: Vec<Event>,
+    batch_size: usize,
+    rows: usize,
+}
+
+impl FrameSink {
+    fn create(
+        path: &str,
+        format: SinkFormat,
+        batch_size: usize,
+        compress: Compression,
+        timestamps: TimestampFormat,
+        typed_payload: TypedPayload,
+    ) -> Result<Self> {
+        Ok(Self {
+            format,
+            compress,
+            timestamps,
+            typed_payload,
+            file: Some(File::create(path)?),
+            writer: None,
+            batch: Vec::with_capacity(batch_size),
+            batch_size,
+            rows: 0,
+        })
+    }
+
+    fn push(&mut self, e: Event) -> Result<()> {
+        self.batch.push(e);
+        if self.batch.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+
+        let df = events_to_dataframe(&self.batch, self.timestamps, self.typed_payload)?;
+        if self.writer.is_none() {
+            let file = self.file.take().expect("Output file is open");
+            self.writer = Some(match self.format {
+                SinkFormat::Parquet => {
+                    let mut writer = ParquetWriter::new(file);
+                    if let Some(codec) = self.compress.parquet() {
+                        writer = writer.with_compression(codec);
+                    }
+                    SinkWriter::Parquet(writer.batched(&df.schema())?)
+                }
+                SinkFormat::Ipc => SinkWriter::Ipc(
+                    IpcWriter::new(file)
+                        .with_compression(self.compress.ipc())
+                        .batched(&df.schema())?,
+                ),
+            });
+        }
+        match self.writer.as_mut().unwrap() {
+            SinkWriter::Parquet(writer) => writer.write_batch(&df)?,
+            SinkWriter::Ipc(writer) => writer.write_batch(&df)?,
+        }
+        self.rows += self.batch.len();
+        self.batch.clear();
+        Ok(())
+    }
+
+    /// Write remaining events and the file footer. Returns the number of rows written.
+    fn finish(mut self) -> Result<usize> {
+        self.flush()?;
+        match self.writer.take() {
+            Some(SinkWriter::Parquet(mut writer)) => {
+                writer.finish()?;
+            }
+            Some(SinkWriter::Ipc(mut writer)) => {
+                writer.finish()?;
+            }
+            None => {}
+        }
+        Ok(self.rows)
+    }
+}
+
+/// Build a data frame with a typed payload struct:
+/// `STRUCT(path, user_agent, text, form_type, fields STRUCT(name, value)[])`, followed by
+/// `score` with [`TypedPayload::Scored`].
+fn events_to_dataframe(
+    events: &[Event],
+    timestamps: TimestampFormat,
+    typed_payload: TypedPayload,
+) -> Result<DataFrame> {
+    let column = |name: &str, f: fn(&Event) -> &str| -> Series {
+        let values: Vec<&str> = events.iter().map(f).collect();
+        Series::new(name, values)
+    };
+    let payloads: Vec<serde_json::Value> = events.iter().map(|e| e.payload.to_json()).collect();
+    let payload_field = |key: &str| -> Series {
+        let values: Vec<Option<&str>> = payloads
+            .iter()
+            .map(|p| p.get(key).and_then(|v| v.as_str()))
+            .collect();
+        Series::new(key, values)
+    };
+
+    let mut fields: ListChunked = payloads
+        .iter()
+        .map(|p| {
+            let fields = p.get("fields")?.as_array()?;
+            let names: Vec<Option<&str>> = fields.iter().map(|f| f["name"].as_str()).collect();
+            let values: Vec<Option<&str>> = fields.iter().map(|f| f["value"].as_str()).collect();
+            let field = StructChunked::new(
+                "",
+                &[Series::new("name", names), Series::new("value", values)],
+            )
+            .ok()?;
+            Some(field.into_series())
+        })
+        .collect();
+    fields.rename("fields");
+
+    let mut payload_fields = vec![
+        payload_field("path"),
+        payload_field("user_agent"),
+        payload_field("text"),
+        payload_field("form_type"),
+        fields.into_series(),
+    ];
+    if typed_payload == TypedPayload::Scored {
+        let scores: Vec<Option<i32>> = events.iter().map(|e| e.payload.score()).collect();
+        payload_fields.push(Series::new("score", scores));
+    }
+    let payload = StructChunked::new("payload", &payload_fields)?;
+
+    let timestamp = match timestamps {
+        TimestampFormat::Native => {
+            let values: Vec<i64> = events.iter().map(|e| e.timestamp.timestamp_micros()).collect();
+            Series::new("timestamp", values)
+                .cast(&DataType::Datetime(TimeUnit::Microseconds, None))?
+        }
+        TimestampFormat::EpochMillis => {
+            let values: Vec<i64> = events.iter().map(|e| e.timestamp.timestamp_millis()).collect();
+            Series::new("timestamp", values)
+        }
+    };
+
+    let df = DataFrame::new(vec![
+        column("id", |e| &e.id),
+        column("session_id", |e| &e.session_id),
+        column("page_id", |e| &e.page_id),
+        timestamp,
+        column("event_type", |e| e.event_type()),
+        payload.into_series(),
+    ])?;
+    Ok(df)
+}
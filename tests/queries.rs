@@ -0,0 +1,482 @@
+use std::{env, fs, path::Path};
+
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use compare_olap_rust::{
+    benchmarks::{self, Benchmark},
+    common::{
+        exec_duck, exec_sqlite, register_sqlite_functions, CellValue, DataDir, ExecOptions,
+        QueryResult, TimestampFormat,
+    },
+    events::{generate_session, Ctx, Event},
+    generate,
+    writers::{start_writers, Compression, TypedPayload, WriteOptions, DEFAULT_BATCH_SIZE},
+};
+use jsonschema::JSONSchema;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const SESSIONS: usize = 100;
+
+/// Expected results of every benchmark, one `<benchmark>.<engine>.json` file per query.
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+struct Dataset {
+    // Keep the directory alive for as long as the connections are open.
+    _dir: TempDir,
+    events: Vec<Event>,
+    sqlite: rusqlite::Connection,
+    duck: duckdb::Connection,
+}
+
+fn generate_events() -> Vec<Event> {
+    let ctx = Ctx::new();
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut now = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+
+    let mut events = vec![];
+    for _ in 0..SESSIONS {
+        let timestamp = now;
+        let secs: i8 = rng.gen();
+        now += chrono::Duration::seconds(secs.abs() as i64);
+        events.extend(generate_session(&ctx, &mut rng, timestamp));
+    }
+    events
+}
+
+fn dataset() -> Dataset {
+    let dir = tempfile::tempdir().unwrap();
+    let events = generate_events();
+
+    let sqlite = rusqlite::Connection::open(dir.path().join("eventsqlite.db")).unwrap();
+    register_sqlite_functions(&sqlite).unwrap();
+    sqlite
+        .execute_batch(
+            r#"
+CREATE TABLE events (
+  id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  page_id TEXT NOT NULL,
+  timestamp TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  payload TEXT
+);
+"#,
+        )
+        .unwrap();
+
+    let duck = duckdb::Connection::open(dir.path().join("eventsduck.db")).unwrap();
+    duck.execute(
+        r#"
+CREATE TABLE events (
+  id VARCHAR NOT NULL,
+  session_id VARCHAR NOT NULL,
+  page_id VARCHAR NOT NULL,
+  timestamp TIMESTAMP NOT NULL,
+  event_type VARCHAR NOT NULL,
+  payload JSON
+);
+"#,
+        [],
+    )
+    .unwrap();
+
+    let insert = r#"
+INSERT INTO events (id, session_id, page_id, timestamp, event_type, payload)
+  VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#;
+    for e in &events {
+        let payload = e.payload.to_json().to_string();
+        sqlite
+            .execute(
+                insert,
+                rusqlite::params![
+                    e.id,
+                    e.session_id,
+                    e.page_id,
+                    e.timestamp,
+                    e.event_type(),
+                    payload,
+                ],
+            )
+            .unwrap();
+        duck.execute(
+            insert,
+            duckdb::params![
+                e.id,
+                e.session_id,
+                e.page_id,
+                e.timestamp,
+                e.event_type(),
+                payload,
+            ],
+        )
+        .unwrap();
+    }
+
+    Dataset {
+        _dir: dir,
+        events,
+        sqlite,
+        duck,
+    }
+}
+
+fn bench(name: &str) -> Box<dyn Benchmark> {
+    benchmarks::all()
+        .into_iter()
+        .find(|b| b.name() == name)
+        .unwrap_or_else(|| panic!("Unknown benchmark {name}"))
+}
+
+fn run_sqlite(data: &Dataset, name: &str) -> QueryResult {
+    let sql = bench(name).sqlite_sql().unwrap();
+    exec_sqlite(&data.sqlite, name, sql, &ExecOptions::default()).unwrap()
+}
+
+fn run_duck(data: &Dataset, name: &str) -> QueryResult {
+    let bench = bench(name);
+    let sql = bench.duck_sql().unwrap();
+    exec_duck(&data.duck, name, sql, bench.duck_columns(), &ExecOptions::default()).unwrap()
+}
+
+fn int(v: &CellValue) -> i64 {
+    match v {
+        CellValue::Int(n) => *n,
+        other => panic!("Expected an integer, got {other:?}"),
+    }
+}
+
+fn float(v: &CellValue) -> f64 {
+    match v {
+        CellValue::Int(n) => *n as f64,
+        CellValue::Float(n) => *n,
+        other => panic!("Expected a number, got {other:?}"),
+    }
+}
+
+#[test]
+fn every_benchmark_runs() {
+    let data = dataset();
+    let opts = ExecOptions::default();
+
+    for bench in benchmarks::all() {
+        if let Some(sql) = bench.sqlite_sql() {
+            exec_sqlite(&data.sqlite, bench.name(), sql, &opts)
+                .unwrap_or_else(|err| panic!("{} failed on SQLite: {err:#}", bench.name()));
+        }
+        if let Some(sql) = bench.duck_sql() {
+            exec_duck(&data.duck, bench.name(), sql, bench.duck_columns(), &opts)
+                .unwrap_or_else(|err| panic!("{} failed on DuckDB: {err:#}", bench.name()));
+        }
+    }
+}
+
+#[test]
+fn count_by_event_type_sums_to_total() {
+    let data = dataset();
+
+    for result in [
+        run_sqlite(&data, "count_by_event_type"),
+        run_duck(&data, "count_by_event_type"),
+    ] {
+        let total: i64 = result.rows.iter().map(|row| int(&row[1])).sum();
+        assert_eq!(total, data.events.len() as i64);
+    }
+}
+
+#[test]
+fn top_pages_returns_at_most_five() {
+    let data = dataset();
+
+    for result in [run_sqlite(&data, "top_pages"), run_duck(&data, "top_pages")] {
+        assert!(!result.rows.is_empty());
+        assert!(result.rows.len() <= 5);
+
+        let counts: Vec<i64> = result.rows.iter().map(|row| int(&row[1])).collect();
+        assert!(counts.windows(2).all(|w| w[0] >= w[1]), "not sorted: {counts:?}");
+    }
+}
+
+#[test]
+fn avg_feedback_score_is_within_range() {
+    let data = dataset();
+
+    for result in [
+        run_sqlite(&data, "avg_feedback_score"),
+        run_duck(&data, "avg_feedback_score"),
+    ] {
+        assert_eq!(result.rows.len(), 1);
+        let average = float(&result.rows[0][0]);
+        assert!((0.0..=100.0).contains(&average), "average {average}");
+    }
+}
+
+#[test]
+fn avg_page_loads_per_session_is_within_bounds() {
+    let data = dataset();
+
+    for result in [
+        run_sqlite(&data, "avg_page_loads_per_session"),
+        run_duck(&data, "avg_page_loads_per_session"),
+    ] {
+        let row = &result.rows[0];
+        let (avg, min, max) = (float(&row[0]), int(&row[1]), int(&row[2]));
+        assert!(min >= 1 && max <= 12, "min {min}, max {max}");
+        assert!(min as f64 <= avg && avg <= max as f64);
+    }
+}
+
+/// Result as JSON with floats rounded to 6 decimals, so that averages summed up in a different
+/// order on another platform still match.
+fn snapshot(result: &QueryResult) -> Value {
+    let rows: Vec<Vec<Value>> = result
+        .rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|v| match v {
+                    CellValue::Null => Value::Null,
+                    CellValue::Int(n) => json!(n),
+                    CellValue::Float(n) => json!((n * 1e6).round() / 1e6),
+                    CellValue::Text(s) => json!(s),
+                    CellValue::Bytes(len) => json!({ "bytes": len }),
+                    CellValue::Unreadable => json!({ "unreadable": true }),
+                })
+                .collect()
+        })
+        .collect();
+    json!({ "columns": result.columns, "rows": rows })
+}
+
+/// Compare the result with its golden file and return a description of the difference.
+/// A missing golden file is a difference too. `UPDATE_GOLDEN=1` writes all of them instead:
+///
+/// ```text
+/// UPDATE_GOLDEN=1 cargo test --test queries results_match_golden_files
+/// ```
+fn check_golden(name: &str, result: &QueryResult) -> Option<String> {
+    let path = Path::new(GOLDEN_DIR).join(format!("{name}.json"));
+    let actual = snapshot(result);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(GOLDEN_DIR).unwrap();
+        fs::write(&path, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+        return None;
+    }
+    if !path.exists() {
+        return Some(format!("{name}: missing golden file {}", path.display()));
+    }
+
+    let expected: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+        .unwrap_or_else(|err| panic!("Invalid golden file {}: {err}", path.display()));
+    (expected != actual).then(|| format!("{name}:\n  expected {expected}\n  actual   {actual}"))
+}
+
+#[test]
+fn results_match_golden_files() {
+    let data = dataset();
+    let opts = ExecOptions::default();
+
+    let mut mismatches = vec![];
+    for bench in benchmarks::all() {
+        if let Some(sql) = bench.sqlite_sql() {
+            let result = exec_sqlite(&data.sqlite, bench.name(), sql, &opts).unwrap();
+            mismatches.extend(check_golden(&format!("{}.sqlite", bench.name()), &result));
+        }
+        if let Some(sql) = bench.duck_sql() {
+            let result =
+                exec_duck(&data.duck, bench.name(), sql, bench.duck_columns(), &opts).unwrap();
+            mismatches.extend(check_golden(&format!("{}.duck", bench.name()), &result));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "Results differ from the golden files, rerun with UPDATE_GOLDEN=1 if that's \
+         intended:\n{}",
+        mismatches.join("\n")
+    );
+}
+
+/// Expected `payload` of each event type. Form field values are always strings.
+fn payload_schema() -> JSONSchema {
+    let string = json!({ "type": "string" });
+    let schema = json!({
+        "type": "object",
+        "required": ["event_type", "payload"],
+        "allOf": [
+            {
+                "if": { "properties": { "event_type": { "const": "page_load" } } },
+                "then": { "properties": { "payload": {
+                    "type": "object",
+                    "required": ["path", "user_agent"],
+                    "properties": { "path": string, "user_agent": string },
+                    "additionalProperties": false
+                } } }
+            },
+            {
+                "if": { "properties": { "event_type": { "const": "chat_message" } } },
+                "then": { "properties": { "payload": {
+                    "type": "object",
+                    "required": ["text"],
+                    "properties": { "text": string },
+                    "additionalProperties": false
+                } } }
+            },
+            {
+                "if": { "properties": { "event_type": { "const": "form_submit" } } },
+                "then": { "properties": { "payload": {
+                    "type": "object",
+                    "required": ["form_type", "fields"],
+                    "properties": {
+                        "form_type": { "enum": ["feedback", "contact-us"] },
+                        "fields": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": {
+                                "type": "object",
+                                "required": ["name", "value"],
+                                "properties": { "name": string, "value": string },
+                                "additionalProperties": false
+                            }
+                        }
+                    },
+                    "additionalProperties": false
+                } } }
+            },
+            {
+                "properties": {
+                    "event_type": { "enum": ["page_load", "chat_message", "form_submit"] }
+                }
+            }
+        ]
+    });
+    JSONSchema::compile(&schema).expect("Payload schema is valid")
+}
+
+/// Schema violations of `payloads`, each as `<event_type> <payload>: <error>`.
+fn schema_errors(payloads: impl IntoIterator<Item = (String, Value)>) -> Vec<String> {
+    let schema = payload_schema();
+    let mut errors = vec![];
+    for (event_type, payload) in payloads {
+        let instance = json!({ "event_type": event_type, "payload": payload });
+        if let Err(errs) = schema.validate(&instance) {
+            errors.extend(errs.map(|err| format!("{event_type} {payload}: {err}")));
+        }
+    }
+    errors
+}
+
+/// Struct payloads have every field of every event type, the ones of other types are NULL.
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(strip_nulls).collect()),
+        other => other,
+    }
+}
+
+#[test]
+fn generated_payloads_match_schema() {
+    let payloads = generate_events()
+        .into_iter()
+        .map(|e| (e.event_type().to_string(), e.payload.to_json()));
+
+    let errors = schema_errors(payloads);
+    assert!(errors.is_empty(), "Invalid payloads:\n{}", errors.join("\n"));
+}
+
+#[test]
+fn typed_duck_payloads_match_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let events = generate_events();
+    let options = WriteOptions {
+        data_dir: DataDir::create(dir.path()).unwrap(),
+        timestamps: TimestampFormat::Native,
+        append: false,
+        busy_retries: 0,
+        batch_size: DEFAULT_BATCH_SIZE,
+        compress: Compression::None,
+        ndjson: false,
+        arrow: false,
+        typed_payload: TypedPayload::Strings,
+        parquet_partition_by: None,
+        in_memory: true,
+    };
+    let writers = start_writers(&options, ExecOptions::default()).unwrap();
+    for event in &events {
+        writers.send(event.clone());
+    }
+    let databases = writers.finish().unwrap();
+
+    let mut stmt = databases
+        .duck_typed
+        .prepare("SELECT event_type, CAST(to_json(payload) AS VARCHAR) FROM events")
+        .unwrap();
+    let payloads: Vec<(String, Value)> = stmt
+        .query_map([], |row| {
+            let payload: String = row.get(1)?;
+            Ok((row.get(0)?, strip_nulls(serde_json::from_str(&payload).unwrap())))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(payloads.len(), events.len());
+
+    let errors = schema_errors(payloads);
+    assert!(errors.is_empty(), "Invalid typed payloads:\n{}", errors.join("\n"));
+}
+
+/// Timestamps in milliseconds of the Parquet file written by the generator with `extra`
+/// arguments, in file order. Four producer threads interleave their sessions.
+fn generated_timestamps(extra: &[&str]) -> Vec<i64> {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_str().unwrap();
+    let argv = [
+        "gen_data",
+        "--sessions",
+        "500",
+        "--seed",
+        "7",
+        "--threads",
+        "4",
+        "--in-memory",
+        "--no-progress",
+        "--data-dir",
+        data_dir,
+    ];
+    let args = generate::Args::parse_from(argv.iter().chain(extra));
+    generate::generate(&args).unwrap();
+
+    let parquet = dir.path().join("events-typed.parquet");
+    let duck = duckdb::Connection::open_in_memory().unwrap();
+    let mut stmt = duck
+        .prepare(&format!(
+            "SELECT epoch_ms(timestamp) FROM read_parquet('{}')",
+            parquet.display()
+        ))
+        .unwrap();
+    let timestamps = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    timestamps
+}
+
+#[test]
+fn generated_events_are_sorted_unless_shuffled() {
+    let sorted = |timestamps: &[i64]| timestamps.windows(2).all(|w| w[0] <= w[1]);
+
+    let clustered = generated_timestamps(&[]);
+    assert!(!clustered.is_empty());
+    assert!(sorted(&clustered), "Events aren't in timestamp order");
+
+    let shuffled = generated_timestamps(&["--shuffle"]);
+    assert_eq!(shuffled.len(), clustered.len());
+    assert!(!sorted(&shuffled), "Shuffled events are in timestamp order");
+}